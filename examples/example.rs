@@ -1,10 +1,12 @@
+use std::num::NonZeroUsize;
+
 use wurfl::*;
 
 fn main() {
     println!("Starting WURFL wrapper usage sample!");
     let wurfl_path = "/usr/share/wurfl/wurfl.zip";
     let wurfl_res = Wurfl::new(wurfl_path, None, None,
-                               WurflCacheProvider::LRU, Some("100000"));
+                               WurflCacheProvider::Lru { size: NonZeroUsize::new(100000).unwrap() }, None);
     let engine = match wurfl_res {
         Ok(engine) => engine,
         Err(error) => panic!("Problem initializing wurfl: {:?}", error),
@@ -45,6 +47,12 @@ fn main() {
     };
     println!("Device 2 ID - outside result closure: {}", d2.get_device_id());
 
+    println!("Printing all virtual capabilities of device 2 --------");
+    for (name, value) in d2.virtual_capabilities() {
+        println!("< {}: {} >", name, value);
+    }
+    println!("---------------------------------------------------------");
+
     // Multiple lookup results added to vector
     let uas: &[&str] = &[
         "Mozilla/5.0 (iPhone; CPU iPhone OS 14_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/14.0 Mobile/15E148 Safari/604.1",
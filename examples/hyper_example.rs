@@ -1,11 +1,12 @@
 use std::convert::Infallible;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 
 use hyper::{Body, Request, Response, Server};
 use hyper::service::{make_service_fn, service_fn};
 
-use wurfl::{Wurfl, WurflCacheProvider};
+use wurfl::{SharedWurfl, Wurfl, WurflCacheProvider};
 
 const DEFAULT_WURFL_FILE_PATH: &str = "/usr/share/wurfl/wurfl.zip";
 
@@ -31,14 +32,15 @@ pub async fn main() {
 
     // First, create a Wurfl engine instance with cache provider
     let wurfl_res = Wurfl::new(&*wurfl_path, None, None,
-                               WurflCacheProvider::LRU, Some("100000"));
+                               WurflCacheProvider::Lru { size: NonZeroUsize::new(100000).unwrap() }, None);
     let engine = match wurfl_res {
         Ok(engine) => engine,
         Err(error) => panic!("Problem initializing wurfl: {:?}", error),
     };
     println!("WURFL API Version created: {}", engine.get_api_version());
-    // now make it thread safe
-    let safe_engine = Arc::new(engine);
+    // now make it thread safe. SharedWurfl also allows to swap in a new engine (ie: with SharedWurfl::rebuild_from_path)
+    // while the server is running
+    let safe_engine = Arc::new(SharedWurfl::new(engine));
 
     // A `Service` is needed for every connection, so this
     // creates one wrapping our `detect` function.
@@ -62,8 +64,9 @@ pub async fn main() {
 }
 
 // Actual device detection: returns a string with wurfl_id and virtual capability complete_device_name
-fn detect(_req: Request<Body>, safe_engine: &Arc<Wurfl>) -> String {
-    let device = match safe_engine.lookup_with_headers(_req.headers()) {
+fn detect(_req: Request<Body>, safe_engine: &Arc<SharedWurfl>) -> String {
+    let engine = safe_engine.load();
+    let device = match engine.lookup_with_headers(_req.headers()) {
         Ok(d) => d,
         Err(_) => panic!("Error during lookup")
     };
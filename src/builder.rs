@@ -0,0 +1,440 @@
+/// WurflBuilder collects the WURFL engine settings and creates a `Wurfl` engine with them.
+///
+/// All settings are validated before the WURFL file is loaded: when one or more of them are invalid, `build` returns a
+/// single `WurflError` whose `invalid_settings` field lists every rejected setting.
+#[derive(Clone, Debug, Default)]
+pub struct WurflBuilder {
+    root: Option<String>,
+    #[cfg(target_os = "linux")]
+    root_data: Option<RootData>,
+    patches: Vec<String>,
+    reload_from_memory: bool,
+    requested_capabilities: Vec<String>,
+    cache_provider: Option<WurflCacheProvider>,
+    // size of the deprecated LRU cache provider, as passed to Wurfl::new
+    legacy_cache_extra_config: Option<String>,
+    engine_target: Option<EngineTarget>,
+    useragent_priority: Option<UserAgentPriority>,
+    attributes: Vec<(WurflAttribute, bool)>,
+    log_path: Option<String>,
+    updater_log_path: Option<String>,
+    #[cfg(feature = "tracing")]
+    trace_logs: bool,
+}
+
+impl WurflBuilder {
+    /// Creates a builder with no settings. The root WURFL file must be set with `root` before calling `build`
+    pub fn new() -> WurflBuilder {
+        WurflBuilder::default()
+    }
+
+    /// Sets the path to the wurfl.xml/zip file
+    pub fn root(mut self, wurfl_xml: &str) -> WurflBuilder {
+        self.root = Some(wurfl_xml.to_string());
+        #[cfg(target_os = "linux")]
+        {
+            self.root_data = None;
+        }
+        self
+    }
+
+    /// Sets the WURFL data to load from memory, in place of a root file path (Linux only).
+    /// `build` hands the data to libwurfl through an anonymous memory file (`memfd_create`), passed as
+    /// `/proc/self/fd/N`: the data never sits on a filesystem path. The memory file is kept until the engine is
+    /// destroyed, so that the root path known by libwurfl stays valid.
+    /// An engine built this way has no root file to update or rebuild from: update it with `Wurfl::reload_from_bytes`.
+    #[cfg(target_os = "linux")]
+    pub fn root_from_bytes(mut self, data: &[u8], format: DataFormat) -> WurflBuilder {
+        self.root_data = Some(RootData { data: Arc::from(data), format });
+        self.root = None;
+        self
+    }
+
+    /// Adds the path of a patch file to load on top of the root file
+    pub fn patch(mut self, patch: &str) -> WurflBuilder {
+        self.patches.push(patch.to_string());
+        self
+    }
+
+    /// Adds the paths of the patch files to load on top of the root file
+    pub fn patches(mut self, patches: &[&str]) -> WurflBuilder {
+        self.patches.extend(patches.iter().map(|p| p.to_string()));
+        self
+    }
+
+    /// Registers the patches so that they are applied to the data passed to `Wurfl::reload_from_bytes` too.
+    /// Required to reload from memory an engine loaded with patches
+    pub fn enable_reload_from_memory(mut self, enabled: bool) -> WurflBuilder {
+        self.reload_from_memory = enabled;
+        self
+    }
+
+    /// Adds a capability to load; when at least one is added, the engine loads only the requested capabilities
+    pub fn requested_capability(mut self, cap_name: &str) -> WurflBuilder {
+        self.requested_capabilities.push(cap_name.to_string());
+        self
+    }
+
+    /// Adds the capabilities to load; when at least one is added, the engine loads only the requested capabilities
+    pub fn requested_capabilities(mut self, cap_names: &[&str]) -> WurflBuilder {
+        self.requested_capabilities.extend(cap_names.iter().map(|c| c.to_string()));
+        self
+    }
+
+    /// Sets the cache provider, along with its cache sizes
+    pub fn cache_provider(mut self, cache_provider: WurflCacheProvider) -> WurflBuilder {
+        self.cache_provider = Some(cache_provider);
+        self
+    }
+
+    /// Sets the size of the deprecated LRU cache provider, in the form "100000"
+    fn legacy_cache_extra_config(mut self, cache_extra_config: Option<&str>) -> WurflBuilder {
+        self.legacy_cache_extra_config = cache_extra_config.map(|c| c.to_string());
+        self
+    }
+
+    /// Sets the engine target
+    pub fn engine_target(mut self, engine_target: EngineTarget) -> WurflBuilder {
+        self.engine_target = Some(engine_target);
+        self
+    }
+
+    /// Sets the User-Agent priority
+    pub fn useragent_priority(mut self, useragent_priority: UserAgentPriority) -> WurflBuilder {
+        self.useragent_priority = Some(useragent_priority);
+        self
+    }
+
+    /// Enables or disables an engine attribute
+    pub fn attribute(mut self, attribute: WurflAttribute, enabled: bool) -> WurflBuilder {
+        self.attributes.retain(|(a, _)| *a != attribute);
+        self.attributes.push((attribute, enabled));
+        self
+    }
+
+    /// Sets the path of the engine log file
+    pub fn log_path(mut self, log_file: &str) -> WurflBuilder {
+        self.log_path = Some(log_file.to_string());
+        self
+    }
+
+    /// Forwards the engine log lines as `tracing` events (target "wurfl::engine", with an `engine_id` field)
+    /// instead of writing them to a log file
+    #[cfg(feature = "tracing")]
+    pub fn trace_logs(mut self, enabled: bool) -> WurflBuilder {
+        self.trace_logs = enabled;
+        self
+    }
+
+    /// Sets the path of the updater log file, which the wrapper writes from the updater log lines it receives
+    pub fn updater_log_path(mut self, log_file: &str) -> WurflBuilder {
+        self.updater_log_path = Some(log_file.to_string());
+        self
+    }
+
+    /// Validates all the settings, then creates and loads the WURFL engine
+    pub fn build(&self) -> Result<Wurfl, WurflError> {
+        let invalid_settings = self.validate();
+        if !invalid_settings.is_empty() {
+            return Err(WurflError::from_invalid_settings(invalid_settings));
+        }
+
+        #[cfg(target_os = "linux")]
+        let root_file = match &self.root_data {
+            Some(root_data) => Some(MemoryRoot::create(root_data)?),
+            None => None,
+        };
+        #[cfg(not(target_os = "linux"))]
+        let root_file: Option<MemoryRoot> = None;
+        let root = match (&root_file, &self.root) {
+            (Some(rf), _) => rf.path.clone(),
+            (None, Some(root)) => root.clone(),
+            (None, None) => unreachable!("validate checks that the root is set"),
+        };
+
+        let wh = unsafe { wurfl_create() };
+        if wh.is_null() {
+            return Err(WurflError::new(WurflErrorKind::InvalidHandle, "Wurfl handle is NULL".to_string()));
+        }
+
+        // the engine may still reject some values (ie: a corrupted patch file), those are reported all together too
+        let engine_id = next_engine_id();
+        let invalid_settings = self.apply(wh, engine_id, &root);
+        if !invalid_settings.is_empty() {
+            unsafe { wurfl_destroy(wh) };
+            return Err(WurflError::from_invalid_settings(invalid_settings));
+        }
+
+        if unsafe { wurfl_load(wh) } != WURFL_OK {
+            let err = WurflError::from_handle(wh);
+            unsafe { wurfl_destroy(wh) };
+            return Err(err);
+        }
+
+        let engine = Wurfl::from_loaded_handle(wh, engine_id, self, root_file.map(|rf| rf.file));
+        if engine.is_err() {
+            unsafe { wurfl_destroy(wh) };
+        }
+        let engine = engine?;
+        // the updater log lines must reach the sink that writes the updater log file
+        if self.updater_log_path.is_some() {
+            engine.install_updater_log_trampoline()?;
+        }
+        Ok(engine)
+    }
+
+    /// Checks the settings without touching the C library and returns the ones that are invalid
+    fn validate(&self) -> Vec<InvalidSetting> {
+        let mut invalid = Vec::new();
+
+        #[cfg(target_os = "linux")]
+        let root_data = self.root_data.as_ref();
+        #[cfg(not(target_os = "linux"))]
+        let root_data: Option<&RootData> = None;
+        match (&self.root, root_data) {
+            (Some(root), _) => check_file_path("root", root, &mut invalid),
+            (None, Some(rd)) if rd.data.is_empty() => {
+                invalid.push(invalid_setting("root", WurflErrorKind::InvalidParameter, "the WURFL data is empty".to_string()))
+            }
+            (None, Some(_)) => {}
+            (None, None) => invalid.push(invalid_setting("root", WurflErrorKind::RootNotSet, "the WURFL file path is not set".to_string())),
+        }
+        for p in &self.patches {
+            check_file_path("patch", p, &mut invalid);
+        }
+        for cap_name in &self.requested_capabilities {
+            if cap_name.is_empty() {
+                invalid.push(invalid_setting("requested_capability", WurflErrorKind::EmptyId, "capability name is empty".to_string()));
+            } else if cap_name.contains('\0') {
+                invalid.push(invalid_setting("requested_capability", WurflErrorKind::InvalidString, format!("capability name {:?} contains a nul character", cap_name)));
+            }
+        }
+        if let Some(cp) = &self.cache_provider {
+            if let Err(setting) = self.resolve_cache_provider(cp) {
+                invalid.push(setting);
+            }
+        }
+        if let Some(log_path) = &self.log_path {
+            check_log_path("log_path", log_path, &mut invalid);
+            #[cfg(feature = "tracing")]
+            if self.trace_logs {
+                invalid.push(invalid_setting("log_path", WurflErrorKind::InvalidParameter, "engine logs cannot be both written to a file and traced".to_string()));
+            }
+        }
+        if let Some(log_path) = &self.updater_log_path {
+            check_log_path("updater_log_path", log_path, &mut invalid);
+        }
+        invalid
+    }
+
+    /// Passes every setting to the engine handle and returns the ones it rejected.
+    /// Must be called only after `validate` returned no invalid settings.
+    fn apply(&self, wh: wurfl_handle, engine_id: u64, root: &str) -> Vec<InvalidSetting> {
+        #[cfg(not(feature = "tracing"))]
+        let _ = engine_id;
+        let mut invalid = Vec::new();
+        let mut check = |setting: &'static str, we: wurfl_error| {
+            if we != WURFL_OK {
+                let kind = to_error_kind(unsafe { wurfl_get_error_code(wh) });
+                invalid.push(invalid_setting(setting, kind, Wurfl::get_error_message(wh)));
+            }
+        };
+
+        let c_root = to_c_string(root);
+        check("root", unsafe { wurfl_set_root(wh, c_root.as_ptr()) });
+
+        let cache_config = self.cache_provider.as_ref()
+            .and_then(|cp| self.resolve_cache_provider(cp).ok())
+            .and_then(|cp| to_cache_provider(&cp));
+        if let Some((cache_provider, config)) = cache_config {
+            let c_config = to_c_string(&config);
+            check("cache_provider", unsafe { wurfl_set_cache_provider(wh, cache_provider, c_config.as_ptr()) });
+        }
+
+        for p in &self.patches {
+            let c_patch = to_c_string(p);
+            let we = if self.reload_from_memory {
+                unsafe { wurfl_add_patch_enabling_reload_from_memory(wh, c_patch.as_ptr()) }
+            } else {
+                unsafe { wurfl_add_patch(wh, c_patch.as_ptr()) }
+            };
+            check("patch", we);
+        }
+
+        for cap_name in &self.requested_capabilities {
+            let c_cap_name = to_c_string(cap_name);
+            check("requested_capability", unsafe { wurfl_add_requested_capability(wh, c_cap_name.as_ptr()) });
+        }
+
+        if let Some(et) = self.engine_target {
+            check("engine_target", unsafe { wurfl_set_engine_target(wh, to_engine_target(et)) });
+        }
+
+        if let Some(up) = self.useragent_priority {
+            check("useragent_priority", unsafe { wurfl_set_useragent_priority(wh, to_useragent_priority(up)) });
+        }
+
+        for (attr, enabled) in &self.attributes {
+            check("attribute", unsafe { wurfl_set_attr(wh, to_wurfl_attr(*attr), *enabled as i32) });
+        }
+
+        if let Some(log_path) = &self.log_path {
+            let c_log_path = to_c_string(log_path);
+            check("log_path", unsafe { wurfl_set_log_path(wh, c_log_path.as_ptr()) });
+        }
+
+        #[cfg(feature = "tracing")]
+        if self.trace_logs {
+            check("trace_logs", install_engine_log_trampoline(wh, engine_id));
+        }
+
+        invalid
+    }
+
+    /// Returns a copy of the settings that does not keep the in-memory WURFL data alive, to be stored by the engine
+    fn without_root_data(&self) -> WurflBuilder {
+        #[allow(unused_mut)]
+        let mut builder = self.clone();
+        #[cfg(target_os = "linux")]
+        {
+            builder.root_data = None;
+        }
+        builder
+    }
+
+    /// Converts the deprecated LRU cache provider to Lru and checks the cache sizes
+    #[allow(deprecated)]
+    fn resolve_cache_provider(&self, cp: &WurflCacheProvider) -> Result<WurflCacheProvider, InvalidSetting> {
+        let invalid = |reason: String| invalid_setting("cache_provider", WurflErrorKind::InvalidCacheSize, reason);
+        let cp = match cp {
+            WurflCacheProvider::LRU => {
+                let extra_config = match &self.legacy_cache_extra_config {
+                    Some(ec) => ec,
+                    None => return Err(invalid("the LRU cache provider requires its size in cache_extra_config".to_string())),
+                };
+                match extra_config.trim().parse::<NonZeroUsize>() {
+                    Ok(size) => WurflCacheProvider::Lru { size },
+                    Err(_) => return Err(invalid(format!("LRU cache size {:?} is not a positive integer", extra_config))),
+                }
+            }
+            other => other.clone(),
+        };
+        let sizes = match &cp {
+            WurflCacheProvider::Lru { size } => vec![*size],
+            WurflCacheProvider::DoubleLru { useragent_size, device_size } => vec![*useragent_size, *device_size],
+            _ => vec![],
+        };
+        if let Some(size) = sizes.iter().find(|s| s.get() > MAX_CACHE_SIZE) {
+            return Err(invalid(format!("cache size {} exceeds the maximum size {}", size, MAX_CACHE_SIZE)));
+        }
+        Ok(cp)
+    }
+}
+
+/// WURFL data set with `WurflBuilder::root_from_bytes`, shared by the builder clones
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Clone)]
+struct RootData {
+    data: Arc<[u8]>,
+    format: DataFormat,
+}
+
+impl std::fmt::Debug for RootData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RootData")
+            .field("len", &self.data.len())
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+/// An anonymous memory file holding the data set with `WurflBuilder::root_from_bytes`, which libwurfl loads as root
+/// file through its `/proc/self/fd` path
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct MemoryRoot {
+    file: std::fs::File,
+    path: String,
+}
+
+impl MemoryRoot {
+    #[cfg(target_os = "linux")]
+    fn create(root_data: &RootData) -> Result<MemoryRoot, WurflError> {
+        // the name is only shown in /proc, it tells the data format to whoever inspects the process
+        let name = match root_data.format {
+            DataFormat::Plain => "wurfl.xml",
+            DataFormat::Zip => "wurfl.zip",
+            DataFormat::Gz => "wurfl.xml.gz",
+        };
+        let c_name = to_c_string(name);
+        let fd = unsafe { libc::memfd_create(c_name.as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            let msg = format!("Unable to create a memory file for the WURFL data: {}", std::io::Error::last_os_error());
+            return Err(WurflError::new(WurflErrorKind::InputOutputFailure, msg));
+        }
+        let mut file = unsafe { <std::fs::File as std::os::unix::io::FromRawFd>::from_raw_fd(fd) };
+        if let Err(e) = std::io::Write::write_all(&mut file, &root_data.data) {
+            let msg = format!("Unable to write the WURFL data to a memory file: {}", e);
+            return Err(WurflError::new(WurflErrorKind::InputOutputFailure, msg));
+        }
+        Ok(MemoryRoot { file, path: format!("/proc/self/fd/{}", fd) })
+    }
+}
+
+fn invalid_setting(setting: &'static str, kind: WurflErrorKind, reason: String) -> InvalidSetting {
+    InvalidSetting { setting, kind, reason }
+}
+
+/// converts a setting value that has already been checked for nul characters
+fn to_c_string(value: &str) -> CString {
+    CString::new(value).expect("setting values are checked for nul characters before being applied")
+}
+
+fn check_file_path(setting: &'static str, path: &str, invalid: &mut Vec<InvalidSetting>) {
+    if path.contains('\0') {
+        invalid.push(invalid_setting(setting, WurflErrorKind::InvalidString, format!("path {:?} contains a nul character", path)));
+    } else if !std::path::Path::new(path).is_file() {
+        invalid.push(invalid_setting(setting, WurflErrorKind::FileNotFound, format!("file {} does not exist or is not a regular file", path)));
+    }
+}
+
+fn check_log_path(setting: &'static str, path: &str, invalid: &mut Vec<InvalidSetting>) {
+    if path.contains('\0') {
+        invalid.push(invalid_setting(setting, WurflErrorKind::InvalidString, format!("path {:?} contains a nul character", path)));
+        return;
+    }
+    let parent = std::path::Path::new(path).parent();
+    if let Some(dir) = parent.filter(|d| !d.as_os_str().is_empty()) {
+        if !dir.is_dir() {
+            invalid.push(invalid_setting(setting, WurflErrorKind::FileNotFound, format!("directory {} does not exist", dir.display())));
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod memory_root_tests {
+    use super::*;
+
+    #[test]
+    fn memory_root_path_reads_the_data() {
+        let root_data = RootData { data: Arc::from(&b"<wurfl/>"[..]), format: DataFormat::Plain };
+        let memory_root = MemoryRoot::create(&root_data).unwrap();
+        assert!(memory_root.path.starts_with("/proc/self/fd/"));
+        assert_eq!(std::fs::read(&memory_root.path).unwrap(), b"<wurfl/>");
+        // read twice, as libwurfl may open the root file more than once
+        assert_eq!(std::fs::read(&memory_root.path).unwrap(), b"<wurfl/>");
+    }
+
+    #[test]
+    fn empty_data_is_rejected_before_loading() {
+        let invalid = WurflBuilder::new().root_from_bytes(&[], DataFormat::Zip).validate();
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].kind, WurflErrorKind::InvalidParameter);
+    }
+
+    #[test]
+    fn root_replaces_the_data() {
+        let builder = WurflBuilder::new().root_from_bytes(b"<wurfl/>", DataFormat::Plain).root("/nonexistent/wurfl.zip");
+        assert!(builder.root_data.is_none());
+        assert_eq!(builder.validate()[0].kind, WurflErrorKind::FileNotFound);
+    }
+}
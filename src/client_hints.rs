@@ -0,0 +1,324 @@
+/// The User-Agent Client Hints request headers that help the WURFL engine detect Chromium based browsers, which send
+/// a reduced User-Agent string
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ClientHint {
+    /// Sec-CH-UA: the browser brands and their major versions
+    Ua,
+    /// Sec-CH-UA-Full-Version-List: the browser brands and their full versions
+    UaFullVersionList,
+    /// Sec-CH-UA-Mobile: whether the browser runs on a mobile device
+    UaMobile,
+    /// Sec-CH-UA-Platform: the operating system name
+    UaPlatform,
+    /// Sec-CH-UA-Platform-Version: the operating system version
+    UaPlatformVersion,
+    /// Sec-CH-UA-Model: the device model
+    UaModel,
+    /// Sec-CH-UA-Arch: the CPU architecture
+    UaArch,
+    /// Sec-CH-UA-Bitness: the CPU architecture bitness
+    UaBitness,
+}
+
+impl ClientHint {
+    /// Every client hint, in the order used for the Accept-CH response header
+    pub const ALL: [ClientHint; 8] = [
+        ClientHint::Ua,
+        ClientHint::UaFullVersionList,
+        ClientHint::UaMobile,
+        ClientHint::UaPlatform,
+        ClientHint::UaPlatformVersion,
+        ClientHint::UaModel,
+        ClientHint::UaArch,
+        ClientHint::UaBitness,
+    ];
+
+    /// Returns the request header name of this hint
+    pub fn header_name(&self) -> &'static str {
+        match self {
+            ClientHint::Ua => "Sec-CH-UA",
+            ClientHint::UaFullVersionList => "Sec-CH-UA-Full-Version-List",
+            ClientHint::UaMobile => "Sec-CH-UA-Mobile",
+            ClientHint::UaPlatform => "Sec-CH-UA-Platform",
+            ClientHint::UaPlatformVersion => "Sec-CH-UA-Platform-Version",
+            ClientHint::UaModel => "Sec-CH-UA-Model",
+            ClientHint::UaArch => "Sec-CH-UA-Arch",
+            ClientHint::UaBitness => "Sec-CH-UA-Bitness",
+        }
+    }
+
+    /// Returns true for the hints that make the most difference to the detection of a device, which are worth a
+    /// request retry through the Critical-CH response header
+    pub fn is_critical(&self) -> bool {
+        matches!(self, ClientHint::UaFullVersionList | ClientHint::UaPlatformVersion | ClientHint::UaModel)
+    }
+}
+
+/// A browser brand along with its version, as listed by the Sec-CH-UA and Sec-CH-UA-Full-Version-List headers
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BrandVersion {
+    pub brand: String,
+    pub version: String,
+}
+
+/// The User-Agent Client Hints sent with a request. A hint is `None` when its header is missing or its value is not a
+/// valid structured header.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ClientHints {
+    pub brands: Option<Vec<BrandVersion>>,
+    pub full_version_list: Option<Vec<BrandVersion>>,
+    pub mobile: Option<bool>,
+    pub platform: Option<String>,
+    pub platform_version: Option<String>,
+    pub model: Option<String>,
+    pub arch: Option<String>,
+    pub bitness: Option<String>,
+}
+
+impl ClientHints {
+    /// Parses the client hints found in the given request headers
+    pub fn from_headers<H: HeaderSource + ?Sized>(headers: &H) -> ClientHints {
+        let value = |hint: ClientHint| {
+            headers.header_value(hint.header_name())
+                .and_then(|v| std::str::from_utf8(v).ok())
+        };
+        ClientHints {
+            brands: value(ClientHint::Ua).and_then(parse_brand_list),
+            full_version_list: value(ClientHint::UaFullVersionList).and_then(parse_brand_list),
+            mobile: value(ClientHint::UaMobile).and_then(parse_sf_boolean),
+            platform: value(ClientHint::UaPlatform).and_then(parse_sf_string),
+            platform_version: value(ClientHint::UaPlatformVersion).and_then(parse_sf_string),
+            model: value(ClientHint::UaModel).and_then(parse_sf_string),
+            arch: value(ClientHint::UaArch).and_then(parse_sf_string),
+            bitness: value(ClientHint::UaBitness).and_then(parse_sf_string),
+        }
+    }
+
+    /// Returns true if the request carries the given hint
+    pub fn has(&self, hint: ClientHint) -> bool {
+        match hint {
+            ClientHint::Ua => self.brands.is_some(),
+            ClientHint::UaFullVersionList => self.full_version_list.is_some(),
+            ClientHint::UaMobile => self.mobile.is_some(),
+            ClientHint::UaPlatform => self.platform.is_some(),
+            ClientHint::UaPlatformVersion => self.platform_version.is_some(),
+            ClientHint::UaModel => self.model.is_some(),
+            ClientHint::UaArch => self.arch.is_some(),
+            ClientHint::UaBitness => self.bitness.is_some(),
+        }
+    }
+
+    /// Returns true if the browser sent the Sec-CH-UA header, which means it supports User-Agent Client Hints
+    pub fn is_supported(&self) -> bool {
+        self.brands.is_some()
+    }
+
+    /// Returns the hints that the request does not carry
+    pub fn missing(&self) -> Vec<ClientHint> {
+        ClientHint::ALL.iter().copied().filter(|h| !self.has(*h)).collect()
+    }
+
+    /// Returns the value of the Accept-CH response header that asks the browser for every client hint on the next
+    /// requests. Accept-CH replaces the hints the browser stores for the origin, so it always lists all of them,
+    /// including the ones the browser already sends.
+    pub fn accept_ch() -> String {
+        join_header_names(ClientHint::ALL.iter().copied()).unwrap_or_default()
+    }
+
+    /// Returns the value of the Critical-CH response header that makes the browser retry the request with the
+    /// missing critical hints. It is `None` when no critical hint is missing, or when the browser does not support
+    /// client hints at all, because the retry would not carry them anyway.
+    /// Critical-CH must be sent along with the Accept-CH header returned by `ClientHints::accept_ch`.
+    pub fn critical_ch(&self) -> Option<String> {
+        if !self.is_supported() {
+            return None;
+        }
+        join_header_names(self.missing().into_iter().filter(|h| h.is_critical()))
+    }
+
+    /// Returns the Accept-CH response header, along with the Critical-CH one when it is needed, that a server should
+    /// send to get a better detection on the next request
+    pub fn response_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("Accept-CH", ClientHints::accept_ch())];
+        if let Some(critical_ch) = self.critical_ch() {
+            headers.push(("Critical-CH", critical_ch));
+        }
+        headers
+    }
+}
+
+fn join_header_names<T: IntoIterator<Item=ClientHint>>(hints: T) -> Option<String> {
+    let names: Vec<&str> = hints.into_iter().map(|h| h.header_name()).collect();
+    if names.is_empty() {
+        return None;
+    }
+    Some(names.join(", "))
+}
+
+/// Parses a structured header boolean (RFC 8941), ie: "?1"
+fn parse_sf_boolean(value: &str) -> Option<bool> {
+    match value.trim() {
+        "?1" => Some(true),
+        "?0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a structured header string (RFC 8941), ie: "\"Android\""
+fn parse_sf_string(value: &str) -> Option<String> {
+    let mut chars = value.trim().chars();
+    let s = read_sf_string(&mut chars)?;
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(s)
+}
+
+/// Parses a structured header list of strings with a "v" parameter (RFC 8941), as sent by Sec-CH-UA and
+/// Sec-CH-UA-Full-Version-List, ie: "\"Chromium\";v=\"118\", \"Not=A?Brand\";v=\"99\"". An empty list is
+/// handled as a missing header.
+fn parse_brand_list(value: &str) -> Option<Vec<BrandVersion>> {
+    let mut brands = Vec::new();
+    let mut chars = value.trim().chars().peekable();
+    chars.peek()?;
+    while chars.peek().is_some() {
+        let brand = read_sf_string(&mut chars)?;
+        let mut version = String::new();
+        // parameters, only "v" is used
+        loop {
+            skip_spaces(&mut chars);
+            if chars.peek() != Some(&';') {
+                break;
+            }
+            chars.next();
+            skip_spaces(&mut chars);
+            let key: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphanumeric() || "_-.*".contains(*c))).collect();
+            if key.is_empty() {
+                return None;
+            }
+            let param_value = if chars.peek() == Some(&'=') {
+                chars.next();
+                if chars.peek() == Some(&'"') {
+                    read_sf_string(&mut chars)?
+                } else {
+                    std::iter::from_fn(|| chars.next_if(|c| !matches!(c, ';' | ',' | ' ' | '\t'))).collect()
+                }
+            } else {
+                String::new()
+            };
+            if key == "v" {
+                version = param_value;
+            }
+        }
+        brands.push(BrandVersion { brand, version });
+        skip_spaces(&mut chars);
+        match chars.next() {
+            None => break,
+            Some(',') => {
+                skip_spaces(&mut chars);
+                // a trailing comma is invalid
+                chars.peek()?;
+            }
+            Some(_) => return None,
+        }
+    }
+    Some(brands)
+}
+
+/// Reads a quoted structured header string, unescaping \" and \\
+fn read_sf_string<I: Iterator<Item=char>>(chars: &mut I) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => match chars.next()? {
+                c @ ('"' | '\\') => s.push(c),
+                _ => return None,
+            },
+            c if c.is_ascii() && !c.is_ascii_control() => s.push(c),
+            _ => return None,
+        }
+    }
+}
+
+fn skip_spaces<I: Iterator<Item=char>>(chars: &mut std::iter::Peekable<I>) {
+    while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+}
+
+#[cfg(test)]
+mod client_hints_tests {
+    use super::*;
+
+    fn brand(brand: &str, version: &str) -> BrandVersion {
+        BrandVersion { brand: brand.to_string(), version: version.to_string() }
+    }
+
+    #[test]
+    fn parses_brand_lists() {
+        let value = r#""Chromium";v="118", "Google Chrome";v="118", "Not=A?Brand";v="99""#;
+        assert_eq!(parse_brand_list(value),
+                   Some(vec![brand("Chromium", "118"), brand("Google Chrome", "118"), brand("Not=A?Brand", "99")]));
+        let value = r#" "Not_A Brand";v="8.0.0.0" ,"Chromium" ; v="120.0.6099.109";x"#;
+        assert_eq!(parse_brand_list(value), Some(vec![brand("Not_A Brand", "8.0.0.0"), brand("Chromium", "120.0.6099.109")]));
+        assert_eq!(parse_brand_list(r#""Esc\"aped\\";v=1"#), Some(vec![brand("Esc\"aped\\", "1")]));
+        assert_eq!(parse_brand_list(r#""NoVersion""#), Some(vec![brand("NoVersion", "")]));
+    }
+
+    #[test]
+    fn rejects_invalid_brand_lists() {
+        assert_eq!(parse_brand_list(""), None);
+        assert_eq!(parse_brand_list("   "), None);
+        assert_eq!(parse_brand_list(r#""Chromium";v="118","#), None);
+        assert_eq!(parse_brand_list(r#"Chromium;v="118""#), None);
+        assert_eq!(parse_brand_list(r#""Chromium";v="118"#), None);
+        assert_eq!(parse_brand_list(r#""Chromium" "Edge""#), None);
+        assert_eq!(parse_brand_list(r#""Bad\escape""#), None);
+    }
+
+    #[test]
+    fn parses_strings_and_booleans() {
+        assert_eq!(parse_sf_string(r#""Android""#), Some("Android".to_string()));
+        assert_eq!(parse_sf_string(r#""""#), Some(String::new()));
+        assert_eq!(parse_sf_string(r#""Android" x"#), None);
+        assert_eq!(parse_sf_string("Android"), None);
+        assert_eq!(parse_sf_boolean("?1"), Some(true));
+        assert_eq!(parse_sf_boolean(" ?0 "), Some(false));
+        assert_eq!(parse_sf_boolean("1"), None);
+    }
+
+    #[test]
+    fn reports_missing_hints_and_response_headers() {
+        let headers = vec![
+            ("sec-ch-ua", r#""Chromium";v="118", "Not=A?Brand";v="99""#),
+            ("Sec-CH-UA-Mobile", "?1"),
+            ("Sec-CH-UA-Platform", r#""Android""#),
+            ("Sec-CH-UA-Model", r#""Pixel 7""#),
+        ];
+        let hints = ClientHints::from_headers(&headers);
+        assert!(hints.is_supported());
+        assert_eq!(hints.mobile, Some(true));
+        assert_eq!(hints.model, Some("Pixel 7".to_string()));
+        assert_eq!(hints.missing(), vec![ClientHint::UaFullVersionList, ClientHint::UaPlatformVersion,
+                                         ClientHint::UaArch, ClientHint::UaBitness]);
+        let accept_ch = ClientHints::accept_ch();
+        assert!(ClientHint::ALL.iter().all(|h| accept_ch.contains(h.header_name())));
+        assert_eq!(hints.critical_ch(), Some("Sec-CH-UA-Full-Version-List, Sec-CH-UA-Platform-Version".to_string()));
+        assert_eq!(hints.response_headers(), vec![
+            ("Accept-CH", accept_ch),
+            ("Critical-CH", "Sec-CH-UA-Full-Version-List, Sec-CH-UA-Platform-Version".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn no_critical_ch_without_client_hints_support() {
+        let headers = vec![("Sec-CH-UA", ""), ("User-Agent", "Mozilla/5.0 (X11; Linux x86_64; rv:120.0) Gecko/20100101 Firefox/120.0")];
+        let hints = ClientHints::from_headers(&headers);
+        assert!(!hints.is_supported());
+        assert_eq!(hints.missing().len(), ClientHint::ALL.len());
+        assert_eq!(hints.critical_ch(), None);
+        assert_eq!(hints.response_headers(), vec![("Accept-CH", ClientHints::accept_ch())]);
+    }
+}
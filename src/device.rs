@@ -1,23 +1,49 @@
-/// Device provides access to device static and virtual capabilities
-pub struct Device {
-    // we use this attribute to silence the "variable is never read" warning
-    #[allow(dead_code)]
-    wurfl: wurfl_handle,
+/// Device provides access to device static and virtual capabilities.
+/// A device borrows the Wurfl engine that detected it: use `detach` to get a `DetachedDevice` that can be stored
+/// independently from the engine.
+pub struct Device<'w> {
+    // keeps the engine alive until this device is destroyed: borrowed from the Wurfl engine, or shared when detached
+    engine: Cow<'w, Arc<EngineHandle>>,
     device: wurfl_device_handle,
 }
 
+/// A Device that shares the ownership of the engine that detected it, instead of borrowing it: the engine resources
+/// are released only when both the Wurfl engine and all its detached devices are dropped.
+pub type DetachedDevice = Device<'static>;
+
 /// Device provides access to device static and virtual capabilities and other device data
-impl Device {
+impl<'w> Device<'w> {
+    /// Converts this device into a `DetachedDevice`, which is not bound to the lifetime of the Wurfl engine
+    pub fn detach(self) -> DetachedDevice {
+        // the device handle moves to the detached device, so it must not be destroyed here
+        let this = std::mem::ManuallyDrop::new(self);
+        let engine = unsafe { std::ptr::read(&this.engine) };
+        Device {
+            engine: Cow::Owned(engine.into_owned()),
+            device: this.device,
+        }
+    }
+
     /// returns the WURFL device unique ID
-    pub fn get_device_id(&self) -> &str {
+    pub fn get_device_id(&self) -> Cow<'_, str> {
+        to_str(self.get_device_id_cstr().as_ptr())
+    }
+
+    /// returns the WURFL device unique ID, as the raw bytes returned by the C library
+    pub fn get_device_id_cstr(&self) -> &CStr {
         let d_id = unsafe { wurfl_device_get_id(self.device) };
-        return to_str(d_id);
+        to_c_str(d_id)
     }
 
     /// Returns the unique ID of this device ancestor in the WURFL hierarchy
-    pub fn get_root_ID(&self) -> &str {
+    pub fn get_root_ID(&self) -> Cow<'_, str> {
+        to_str(self.get_root_ID_cstr().as_ptr())
+    }
+
+    /// Returns the unique ID of this device ancestor in the WURFL hierarchy, as the raw bytes returned by the C library
+    pub fn get_root_ID_cstr(&self) -> &CStr {
         let d_root_id = unsafe { wurfl_device_get_root_id(self.device) };
-        return to_str(d_root_id);
+        to_c_str(d_root_id)
     }
 
     /// Returns true if this device is a root device in the WURFL hierarchy
@@ -29,26 +55,47 @@ impl Device {
         return true;
     }
 
-    /// Returns the original UserAgent of matched device (the one passed to lookup)
-    pub fn get_original_user_agent(&self) -> &str {
+    /// Returns the original UserAgent of matched device (the one passed to lookup).
+    /// Invalid UTF-8 sequences are replaced with U+FFFD, use `get_original_user_agent_cstr` to get the exact bytes
+    pub fn get_original_user_agent(&self) -> Cow<'_, str> {
+        to_str(self.get_original_user_agent_cstr().as_ptr())
+    }
+
+    /// Returns the original UserAgent of matched device, as the raw bytes returned by the C library
+    pub fn get_original_user_agent_cstr(&self) -> &CStr {
         let d_orig_ua = unsafe { wurfl_device_get_original_useragent(self.device) };
-        return to_str(d_orig_ua);
+        to_c_str(d_orig_ua)
     }
 
     /// Return a normalized version of this device User-Agent
-    pub fn get_normalized_user_agent(&self) -> &str {
+    pub fn get_normalized_user_agent(&self) -> Cow<'_, str> {
+        to_str(self.get_normalized_user_agent_cstr().as_ptr())
+    }
+
+    /// Return a normalized version of this device User-Agent, as the raw bytes returned by the C library
+    pub fn get_normalized_user_agent_cstr(&self) -> &CStr {
         let d_norm_ua = unsafe { wurfl_device_get_normalized_useragent(self.device) };
-        return to_str(d_norm_ua);
+        to_c_str(d_norm_ua)
     }
 
     /// Returns default UserAgent of matched device (might be different from UA passed to lookup)
-    pub fn get_user_agent(&self) -> &str {
+    pub fn get_user_agent(&self) -> Cow<'_, str> {
+        to_str(self.get_user_agent_cstr().as_ptr())
+    }
+
+    /// Returns default UserAgent of matched device, as the raw bytes returned by the C library
+    pub fn get_user_agent_cstr(&self) -> &CStr {
         let d_ua = unsafe { wurfl_device_get_useragent(self.device) };
-        return to_str(d_ua);
+        to_c_str(d_ua)
     }
 
     /// Returns the device value of the capability with the given name
-    pub fn get_capability(&self, capability_name: &str) -> Option<&str> {
+    pub fn get_capability(&self, capability_name: &str) -> Option<Cow<'_, str>> {
+        self.get_capability_cstr(capability_name).map(|cv| to_str(cv.as_ptr()))
+    }
+
+    /// Returns the device value of the capability with the given name, as the raw bytes returned by the C library
+    pub fn get_capability_cstr(&self, capability_name: &str) -> Option<&CStr> {
         let c_str_cap_name = match CString::new(capability_name) {
             Ok(cn) => cn,
             Err(_) => return None
@@ -57,11 +104,17 @@ impl Device {
         if cap_value.is_null() {
             return None;
         }
-        return Some(to_str(cap_value));
+        Some(to_c_str(cap_value))
     }
 
     /// Returns the device value of the virtual capability with the given name
-    pub fn get_virtual_capability(&self, virtual_capability_name: &str) -> Option<&str> {
+    pub fn get_virtual_capability(&self, virtual_capability_name: &str) -> Option<Cow<'_, str>> {
+        self.get_virtual_capability_cstr(virtual_capability_name).map(|vcv| to_str(vcv.as_ptr()))
+    }
+
+    /// Returns the device value of the virtual capability with the given name, as the raw bytes returned by the C
+    /// library
+    pub fn get_virtual_capability_cstr(&self, virtual_capability_name: &str) -> Option<&CStr> {
         let c_str_vcap_name = match CString::new(virtual_capability_name) {
             Ok(cn) => cn,
             Err(_) => return None
@@ -70,7 +123,75 @@ impl Device {
         if vcap_value.is_null() {
             return None;
         }
-        return Some(to_str(vcap_value));
+        Some(to_c_str(vcap_value))
+    }
+
+    /// Returns the device value of the capability with the given name converted to bool.
+    /// Returns an error if the capability does not exist or its value is neither "true" nor "false"
+    pub fn get_capability_bool(&self, capability_name: &str) -> Result<bool, WurflError> {
+        let value = self.get_cap_value(capability_name, false)?;
+        parse_capability_bool(capability_name, &value)
+    }
+
+    /// Returns the device value of the capability with the given name converted to i32.
+    /// Returns an error if the capability does not exist or its value is not an integer
+    pub fn get_capability_i32(&self, capability_name: &str) -> Result<i32, WurflError> {
+        let value = self.get_cap_value(capability_name, false)?;
+        parse_capability_i32(capability_name, &value)
+    }
+
+    /// Returns the device value of the virtual capability with the given name converted to bool.
+    /// Returns an error if the virtual capability does not exist or its value is neither "true" nor "false"
+    pub fn get_virtual_capability_bool(&self, virtual_capability_name: &str) -> Result<bool, WurflError> {
+        let value = self.get_cap_value(virtual_capability_name, true)?;
+        parse_capability_bool(virtual_capability_name, &value)
+    }
+
+    /// Returns the device value of the virtual capability with the given name converted to i32.
+    /// Returns an error if the virtual capability does not exist or its value is not an integer
+    pub fn get_virtual_capability_i32(&self, virtual_capability_name: &str) -> Result<i32, WurflError> {
+        let value = self.get_cap_value(virtual_capability_name, true)?;
+        parse_capability_i32(virtual_capability_name, &value)
+    }
+
+    /// Returns the value of a static or virtual capability, or an error whose kind tells why it is not available
+    fn get_cap_value(&self, name: &str, virtual_cap: bool) -> Result<Cow<'_, str>, WurflError> {
+        let c_name = match CString::new(name) {
+            Ok(cn) => cn,
+            Err(_) => {
+                let msg = format!("Unable to convert into a CString capability name  {}", name);
+                return Err(WurflError::new(WurflErrorKind::InvalidString, msg));
+            }
+        };
+        let mut we: wurfl_error = WURFL_OK;
+        let value = unsafe {
+            if virtual_cap {
+                wurfl_device_get_virtual_cap(self.device, c_name.as_ptr(), &mut we)
+            } else {
+                wurfl_device_get_static_cap(self.device, c_name.as_ptr(), &mut we)
+            }
+        };
+        if we != WURFL_OK || value.is_null() {
+            let kind = match we {
+                WURFL_OK if virtual_cap => WurflErrorKind::VirtualCapabilityNotFound,
+                WURFL_OK => WurflErrorKind::CapabilityNotFound,
+                _ => to_error_kind(we),
+            };
+            return Err(WurflError::new(kind, format!("Unable to get the value of capability {}", name)));
+        }
+        Ok(to_str(value))
+    }
+
+    /// Returns an iterator over the (name, value) pairs of all the capabilities of this device
+    pub fn capabilities(&self) -> CapabilityIter<'_> {
+        let handle = unsafe { wurfl_device_get_capability_enumerator(self.device) };
+        CapabilityIter { handle, _device: PhantomData }
+    }
+
+    /// Returns an iterator over the (name, value) pairs of all the virtual capabilities of this device
+    pub fn virtual_capabilities(&self) -> CapabilityIter<'_> {
+        let handle = unsafe { wurfl_device_get_virtual_capability_enumerator(self.device) };
+        CapabilityIter { handle, _device: PhantomData }
     }
 
     /// Returns a map -> key:cap_name,value:cap_value for the calling device
@@ -81,21 +202,13 @@ impl Device {
             // this way string doesn't take ownership of cap_name
             let c_str_cap_name = match CString::new(cap_name.clone()) {
                 Ok(cn) => cn,
-                Err(_) => return Err(WurflError { msg: msg }),  // failed to convert to C string
+                Err(_) => return Err(WurflError::new(WurflErrorKind::InvalidString, msg)),  // failed to convert to C string
             };
 
             let cap_ptr = unsafe { wurfl_device_get_capability(self.device, c_str_cap_name.as_ptr()) };
             if !cap_ptr.is_null() {
-                let c_str = unsafe { CStr::from_ptr(cap_ptr) };
-                // the string coming from get_capability should be UTF-8 compliant
-                let cap_value = match c_str.to_str() {
-                    Ok(cv) => cv.to_string(),
-                    Err(utf_err) => {
-                        let emsg = format!("Capability value for {} is an invalid UTF-8 string, it was valid until character {}", &cap_name, utf_err.valid_up_to());
-                        return Err(WurflError { msg: emsg });
-                    }
-                };
-                caps.insert(cap_name.to_string(), cap_value);
+                // decoded lossily, as get_capability does
+                caps.insert(cap_name.to_string(), to_owned_string(cap_ptr));
             }
         };
         return Ok(caps);
@@ -109,44 +222,146 @@ impl Device {
             // this way string doesn't take ownership of cap_name
             let c_str_vcap_name = match CString::new(vcap_name.clone()) {
                 Ok(vcn) => vcn,
-                Err(_) => return Err(WurflError { msg: msg }),  // failed to convert to C string
+                Err(_) => return Err(WurflError::new(WurflErrorKind::InvalidString, msg)),  // failed to convert to C string
             };
             let vcap_ptr = unsafe { wurfl_device_get_virtual_capability(self.device, c_str_vcap_name.as_ptr()) };
             if !vcap_ptr.is_null() {
-                let c_str = unsafe { CStr::from_ptr(vcap_ptr) };
-                // the string coming from get_capability should be UTF-8 compliant
-                let vcap_value = match c_str.to_str() {
-                    Ok(cv) => cv.to_string(),
-                    Err(utf_err) => {
-                        let emsg = format!("Virtual capability value for {} is an invalid UTF-8 string, it was valid until characer {}", &vcap_name, utf_err.valid_up_to());
-                        return Err(WurflError { msg: emsg });
-                    }
-                };
-                vcaps.insert(vcap_name.to_string(), vcap_value);
+                // decoded lossily, as get_virtual_capability does
+                vcaps.insert(vcap_name.to_string(), to_owned_string(vcap_ptr));
             }
         };
         return Ok(vcaps);
     }
 
+    /// Returns the name of the matcher that detected this device
+    pub fn get_matcher_name(&self) -> Cow<'_, str> {
+        let matcher = unsafe { wurfl_device_get_matcher_name(self.device) };
+        to_str(matcher)
+    }
+
+    /// Returns the name of the bucket matcher that selected the matcher used to detect this device
+    pub fn get_bucket_matcher_name(&self) -> Cow<'_, str> {
+        let bucket_matcher = unsafe { wurfl_device_get_bucket_matcher_name(self.device) };
+        to_str(bucket_matcher)
+    }
+
+    /// Returns the diagnostic data that explain how this device has been detected
+    pub fn explain(&self) -> MatchExplanation {
+        MatchExplanation {
+            device_id: self.get_device_id().into_owned(),
+            matcher_name: self.get_matcher_name().into_owned(),
+            bucket_matcher_name: self.get_bucket_matcher_name().into_owned(),
+            match_type: self.get_match_type(),
+            original_user_agent: self.get_original_user_agent().into_owned(),
+            normalized_user_agent: self.get_normalized_user_agent().into_owned(),
+        }
+    }
+
     /// Return the type of matching performed to detect this device
     pub fn get_match_type(&self) -> MatchType {
         let mt = unsafe { wurfl_device_get_match_type(self.device) };
-        match mt {
-            0 => MatchType::WurflMatchTypeExact,
-            1 => MatchType::WurflMatchTypeConclusive,
-            2 => MatchType::WurflMatchTypeRecovery,
-            3 => MatchType::WurflMatchTypeCatchall,
-            // Value 4 (performance) is deprecated and shouldn0t be returned anymore by C API.
-            5 => MatchType::WurflMatchTypeNone,
-            6 => MatchType::WurflMatchTypeCached,
-            // Catch all match: any other value is mapped as Match None
-            _ => MatchType::WurflMatchTypeNone
+        to_match_type(mt)
+    }
+}
+
+/// Diagnostic data that explain how a device has been detected, returned by `Device::explain`
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchExplanation {
+    pub device_id: String,
+    pub matcher_name: String,
+    pub bucket_matcher_name: String,
+    pub match_type: MatchType,
+    pub original_user_agent: String,
+    pub normalized_user_agent: String,
+}
+
+/// Iterator over the (name, value) pairs of the static or virtual capabilities of a Device
+pub struct CapabilityIter<'d> {
+    // NULL when the C library could not create the enumerator, the iterator is empty in that case
+    handle: wurfl_device_capability_enumerator_handle,
+    _device: PhantomData<&'d ()>,
+}
+
+impl Iterator for CapabilityIter<'_> {
+    type Item = (String, CapabilityValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.handle.is_null() || unsafe { wurfl_device_capability_enumerator_is_valid(self.handle) } == 0 {
+            return None;
+        }
+        let name = to_owned_string(unsafe { wurfl_device_capability_enumerator_get_name(self.handle) });
+        let value = to_owned_string(unsafe { wurfl_device_capability_enumerator_get_value(self.handle) });
+        unsafe { wurfl_device_capability_enumerator_move_next(self.handle) };
+        Some((name.clone(), CapabilityValue { name, value }))
+    }
+}
+
+impl Drop for CapabilityIter<'_> {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe { wurfl_device_capability_enumerator_destroy(self.handle) };
         }
     }
 }
 
+/// The value of a capability yielded by `CapabilityIter`, with typed accessors
+#[derive(Clone, PartialEq, Debug)]
+pub struct CapabilityValue {
+    // used in the conversion error messages
+    name: String,
+    value: String,
+}
+
+impl CapabilityValue {
+    /// Returns the capability value as a string
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns the capability value converted to bool, or an error if it is neither "true" nor "false"
+    pub fn as_bool(&self) -> Result<bool, WurflError> {
+        parse_capability_bool(&self.name, &self.value)
+    }
+
+    /// Returns the capability value converted to i32, or an error if it is not an integer
+    pub fn as_i32(&self) -> Result<i32, WurflError> {
+        parse_capability_i32(&self.name, &self.value)
+    }
+
+    /// Returns the capability value as an owned string
+    pub fn into_string(self) -> String {
+        self.value
+    }
+}
+
+impl std::fmt::Display for CapabilityValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+/// converts a capability value to bool, WURFL boolean capabilities hold either "true" or "false"
+fn parse_capability_bool(name: &str, value: &str) -> Result<bool, WurflError> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => {
+            let msg = format!("Value {:?} of capability {} is not a boolean", value, name);
+            Err(WurflError::new(WurflErrorKind::InvalidCapabilityValue, msg))
+        }
+    }
+}
+
+/// converts a capability value to i32
+fn parse_capability_i32(name: &str, value: &str) -> Result<i32, WurflError> {
+    value.trim().parse::<i32>().map_err(|_| {
+        let msg = format!("Value {:?} of capability {} is not an integer", value, name);
+        WurflError::new(WurflErrorKind::InvalidCapabilityValue, msg)
+    })
+}
+
 // Device memory deallocation
-impl Drop for Device {
+impl Drop for Device<'_> {
     fn drop(&mut self) {
         //println!("Destroying... WURFL device");
         unsafe { wurfl_device_destroy(self.device) };
@@ -154,7 +369,7 @@ impl Drop for Device {
 }
 
 // makes device (and it internal objects) transferable between threads
-unsafe impl Send for Device {}
+unsafe impl Send for Device<'_> {}
 
 // assumes Device struct thread safety, which, in our case is supported by the underlying Infuze C library
-unsafe impl Sync for Device {}
+unsafe impl Sync for Device<'_> {}
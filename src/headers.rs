@@ -0,0 +1,421 @@
+/// A source of HTTP request headers. Lookups driven by a HeaderSource let the WURFL engine ask only for the headers it
+/// needs, instead of copying all the request headers beforehand.
+///
+/// Every header lookup (`lookup_with_headers` included) handles the values the same way: they are passed to the engine
+/// as raw bytes, even when they are not valid UTF-8, and a value containing a nul character fails the lookup with a
+/// `WurflErrorKind::InvalidString` error.
+pub trait HeaderSource {
+    /// Returns the value of the header with the given name, matched case-insensitively, if the request has it
+    fn header_value(&self, name: &str) -> Option<&[u8]>;
+}
+
+impl<K, V, S> HeaderSource for HashMap<K, V, S> where
+    K: std::borrow::Borrow<str> + Eq + std::hash::Hash,
+    V: AsRef<[u8]>,
+    S: std::hash::BuildHasher {
+    fn header_value(&self, name: &str) -> Option<&[u8]> {
+        // maps usually hold the names as sent or lowercased, the case-insensitive scan is needed only for other cases
+        if let Some(v) = self.get(name) {
+            return Some(v.as_ref());
+        }
+        let lc_name = name.to_ascii_lowercase();
+        if let Some(v) = self.get(lc_name.as_str()) {
+            return Some(v.as_ref());
+        }
+        // keys differing only by case are resolved by their order, not by the map iteration order
+        self.iter()
+            .filter(|(k, _)| k.borrow().eq_ignore_ascii_case(name))
+            .min_by_key(|(k, _)| k.borrow())
+            .map(|(_, v)| v.as_ref())
+    }
+}
+
+impl<K, V> HeaderSource for [(K, V)] where
+    K: AsRef<str>,
+    V: AsRef<[u8]> {
+    fn header_value(&self, name: &str) -> Option<&[u8]> {
+        self.iter()
+            .find(|(k, _)| k.as_ref().eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_ref())
+    }
+}
+
+impl<K, V> HeaderSource for Vec<(K, V)> where
+    K: AsRef<str>,
+    V: AsRef<[u8]> {
+    fn header_value(&self, name: &str) -> Option<&[u8]> {
+        self.as_slice().header_value(name)
+    }
+}
+
+#[cfg(feature = "http")]
+impl HeaderSource for http::HeaderMap {
+    fn header_value(&self, name: &str) -> Option<&[u8]> {
+        self.get(name).map(|v| v.as_bytes())
+    }
+}
+
+/// The names of the HTTP request headers used by a WURFL engine for device detection, returned by
+/// `Wurfl::important_header_names`. Names are kept in their canonical case (ie: "User-Agent", "Sec-CH-UA-Platform")
+/// and compared case-insensitively.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ImportantHeaderNames {
+    names: Vec<String>,
+}
+
+impl ImportantHeaderNames {
+    /// Returns the header names in canonical case
+    pub fn iter(&self) -> impl Iterator<Item=&str> {
+        self.names.iter().map(|n| n.as_str())
+    }
+
+    /// Returns the number of important headers
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns true when the engine uses no header at all
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns true if the header with the given name, matched case-insensitively, is important
+    pub fn contains(&self, name: &str) -> bool {
+        self.canonical(name).is_some()
+    }
+
+    /// Returns the canonical case of the given header name, if the header is important
+    pub fn canonical(&self, name: &str) -> Option<&str> {
+        self.iter().find(|n| n.eq_ignore_ascii_case(name))
+    }
+
+    /// Keeps only the important headers of the given header map or list of pairs, ie: to forward or log only the
+    /// headers used for device detection. Header names keep the case they have in the input.
+    pub fn filter<K, V, T>(&self, headers: T) -> Vec<(K, V)> where
+        K: AsRef<str>,
+        T: IntoIterator<Item=(K, V)> {
+        headers.into_iter()
+            .filter(|(k, _)| self.contains(k.as_ref()))
+            .collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a ImportantHeaderNames {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.names.iter()
+    }
+}
+
+impl Wurfl {
+    /// Returns the names of the HTTP request headers used by this engine for device detection.
+    /// The names are copied, because they are replaced when the engine is reloaded
+    pub fn important_header_names(&self) -> ImportantHeaderNames {
+        self.important_header_table().names.clone()
+    }
+
+    fn important_header_table(&self) -> Arc<ImportantHeaderTable> {
+        Arc::clone(&read_lock(&self.important_header_table))
+    }
+
+    /// Reads the important headers again after the engine has been reloaded in place
+    fn refresh_important_header_table(&self) -> Result<(), WurflError> {
+        let table = Arc::new(ImportantHeaderTable::read(self.wurfl)?);
+        *write_lock(&self.important_header_table) = table;
+        Ok(())
+    }
+}
+
+/// Data passed to header_retrieve_trampoline during a single lookup
+struct HeaderCallbackData<'a, H: HeaderSource + ?Sized> {
+    source: &'a H,
+    // the C strings handed to libwurfl, which must stay valid until the lookup returns
+    values: RefCell<Vec<CString>>,
+    // the first header whose value could not be passed as a C string, which fails the lookup
+    invalid_header: RefCell<Option<String>>,
+    // the first header whose retrieval panicked, which fails the lookup
+    panicked_header: RefCell<Option<String>>,
+}
+
+/// Called by libwurfl for each header it needs during a lookup. Returns NULL when the header is missing, or when its
+/// value cannot be passed as a C string, or when the header source panics: both are recorded to fail the lookup.
+unsafe extern "C" fn header_retrieve_trampoline<H: HeaderSource + ?Sized>(header_name: *const c_char,
+                                                                          callback_data: *const std::os::raw::c_void) -> *const c_char {
+    if header_name.is_null() || callback_data.is_null() {
+        return std::ptr::null();
+    }
+    let data = &*(callback_data as *const HeaderCallbackData<H>);
+    let value = catch_ffi_panic(|| {
+        let name = to_str(header_name);
+        let value = data.source.header_value(&name)?;
+        let c_value = match CString::new(value) {
+            Ok(v) => v,
+            Err(_) => {
+                data.invalid_header.borrow_mut().get_or_insert_with(|| name.into_owned());
+                return None;
+            }
+        };
+        // moving the CString into the vector does not move its heap buffer, so the pointer stays valid
+        let ptr = c_value.as_ptr();
+        data.values.borrow_mut().push(c_value);
+        Some(ptr)
+    });
+    match value {
+        Some(Some(ptr)) => ptr,
+        Some(None) => std::ptr::null(),
+        None => {
+            data.panicked_header.borrow_mut().get_or_insert_with(|| to_owned_string(header_name));
+            std::ptr::null()
+        }
+    }
+}
+
+impl Wurfl {
+    /// Retrieves device data based on the HTTP request headers provided by a `HeaderSource`: the engine asks the source
+    /// only for the headers it needs.
+    pub fn lookup_with_header_source<H: HeaderSource + ?Sized>(&self, headers: &H) -> Result<Device<'_>, WurflError> {
+        self.lookup_with_header_callback(None, headers)
+    }
+
+    /// Retrieves device data based on a WURFL device ID and the HTTP request headers provided by a `HeaderSource`:
+    /// the engine asks the source only for the headers it needs, exactly as `lookup_with_header_source` does.
+    pub fn lookup_device_id_with_header_source<H: HeaderSource + ?Sized>(&self, device_id: &str, headers: &H) -> Result<Device<'_>, WurflError> {
+        let c_dev_id = match CString::new(device_id) {
+            Ok(did) => did,
+            Err(_) => {
+                let msg = format!("Unable to convert device id  {}! into a CString", device_id);
+                return Err(WurflError::new(WurflErrorKind::InvalidString, msg));
+            }
+        };
+        self.lookup_with_header_callback(Some(&c_dev_id), headers)
+    }
+
+    /// Shared implementation of the HeaderSource lookups: with a device ID the engine only refines that device
+    /// using the headers, otherwise it detects the device from the headers
+    fn lookup_with_header_callback<H: HeaderSource + ?Sized>(&self, device_id: Option<&CStr>, headers: &H) -> Result<Device<'_>, WurflError> {
+        let data = HeaderCallbackData {
+            source: headers,
+            values: RefCell::new(Vec::new()),
+            invalid_header: RefCell::new(None),
+            panicked_header: RefCell::new(None),
+        };
+        let data_ptr = &data as *const HeaderCallbackData<H> as *const std::os::raw::c_void;
+        let callback: wurfl_header_retrieve_callback = Some(header_retrieve_trampoline::<H>);
+        let d_handle = unsafe {
+            match device_id {
+                Some(did) => wurfl_get_device_with_headers(self.wurfl, did.as_ptr(), callback, data_ptr),
+                None => wurfl_lookup(self.wurfl, callback, data_ptr),
+            }
+        };
+        // wrapped first, so that a device returned along with an invalid header is released
+        let device = self.wrap_device_handle(d_handle);
+        if let Some(name) = data.panicked_header.into_inner() {
+            let msg = format!("The header source panicked while retrieving header {}", name);
+            return Err(WurflError::new(WurflErrorKind::HeaderSourcePanicked, msg));
+        }
+        if let Some(name) = data.invalid_header.into_inner() {
+            let msg = format!("Unable to convert the value of header {} to C string", name);
+            return Err(WurflError::new(WurflErrorKind::InvalidString, msg));
+        }
+        device
+    }
+}
+
+/// A reusable set of important HTTP request headers, created by `Wurfl::important_headers`.
+///
+/// The set can be built once and passed to several lookups, then cleared and filled again. Headers that the engine
+/// does not consider important are ignored.
+///
+/// libwurfl has no call to remove a header, so a set is emptied by swapping its C object for an unused one. The
+/// engine keeps a pool of unused C objects, filled ahead of time with `Wurfl::reserve_important_headers` and by the
+/// sets dropped without any header: the sets take their objects from it, and call libwurfl only when it is empty.
+pub struct ImportantHeaders<'w> {
+    wurfl: &'w Wurfl,
+    handle: wurfl_important_header_handle,
+    // the data generation the C object was created in
+    generation: u64,
+    // true when a header has been set since the C object was created
+    has_headers: bool,
+}
+
+impl<'w> ImportantHeaders<'w> {
+    /// Sets the value of a header, matched case-insensitively. Headers that are not important for the engine are
+    /// ignored; values containing a nul character are rejected.
+    pub fn set<V: AsRef<[u8]>>(&mut self, name: &str, value: V) -> Result<(), WurflError> {
+        let table = self.wurfl.important_header_table();
+        let c_header_name = match table.cstring_names.get(&name.to_ascii_lowercase()) {
+            Some(n) => n.as_c_str(),
+            None => return Ok(()),
+        };
+        let c_value = match CString::new(value.as_ref()) {
+            Ok(v) => v,
+            Err(_) => {
+                let msg = format!("Unable to convert the value of header {} to C string", name);
+                return Err(WurflError::new(WurflErrorKind::InvalidString, msg));
+            }
+        };
+        unsafe { wurfl_important_header_set(self.handle, c_header_name.as_ptr(), c_value.as_ptr()) };
+        self.has_headers = true;
+        Ok(())
+    }
+
+    /// Sets all the given headers, stopping at the first value that is rejected
+    pub fn extend_from<K, V, T>(&mut self, headers: T) -> Result<(), WurflError> where
+        K: AsRef<str>,
+        V: AsRef<[u8]>,
+        T: IntoIterator<Item=(K, V)> {
+        for (key, value) in headers {
+            self.set(key.as_ref(), value)?;
+        }
+        Ok(())
+    }
+
+    /// Returns true when no important header has been set since the last clear
+    pub fn is_empty(&self) -> bool {
+        !self.has_headers
+    }
+
+    /// Removes all the headers, so that the set can be filled again for another request.
+    /// The C object is replaced with an unused one taken from the engine pool. A set created before the engine was
+    /// reloaded in place can be used again once cleared.
+    pub fn clear(&mut self) -> Result<(), WurflError> {
+        if !self.has_headers && self.generation == self.wurfl.data_generation() {
+            return Ok(());
+        }
+        let (generation, cih) = take_important_header(self.wurfl)?;
+        unsafe { wurfl_important_header_destroy(self.handle) };
+        self.handle = cih;
+        self.generation = generation;
+        self.has_headers = false;
+        Ok(())
+    }
+}
+
+impl Drop for ImportantHeaders<'_> {
+    fn drop(&mut self) {
+        // a C object without headers is as good as a new one
+        if !self.has_headers {
+            lock(&self.wurfl.engine.important_header_pool).push((self.generation, self.handle));
+            return;
+        }
+        unsafe { wurfl_important_header_destroy(self.handle) };
+    }
+}
+
+/// Returns an unused C important header object of the current data generation, taken from the engine pool or
+/// created when the pool has none. Pooled objects of previous generations are destroyed on the way.
+fn take_important_header(wurfl: &Wurfl) -> Result<(u64, wurfl_important_header_handle), WurflError> {
+    let generation = wurfl.data_generation();
+    let mut stale = Vec::new();
+    let mut pooled = None;
+    {
+        let mut pool = lock(&wurfl.engine.important_header_pool);
+        while let Some((g, cih)) = pool.pop() {
+            if g == generation {
+                pooled = Some(cih);
+                break;
+            }
+            stale.push(cih);
+        }
+    }
+    for cih in stale {
+        unsafe { wurfl_important_header_destroy(cih) };
+    }
+    match pooled {
+        Some(cih) => Ok((generation, cih)),
+        None => Ok((generation, create_important_header(wurfl)?)),
+    }
+}
+
+fn create_important_header(wurfl: &Wurfl) -> Result<wurfl_important_header_handle, WurflError> {
+    let cih = unsafe { wurfl_important_header_create(wurfl.wurfl) };
+    if cih.is_null() {
+        return Err(WurflError::from_handle(wurfl.wurfl));
+    }
+    Ok(cih)
+}
+
+unsafe impl Send for ImportantHeaders<'_> {}
+
+impl Wurfl {
+    /// Returns an empty set of important headers for this engine, taking its C object from the engine pool
+    pub fn important_headers(&self) -> Result<ImportantHeaders<'_>, WurflError> {
+        let (generation, handle) = take_important_header(self)?;
+        Ok(ImportantHeaders { wurfl: self, handle, generation, has_headers: false })
+    }
+
+    /// Creates C important header objects ahead of time, until the engine pool holds at least `count` of them.
+    /// High-QPS workers can call it at startup, or from a background task, so that `important_headers` and
+    /// `ImportantHeaders::clear` do not call libwurfl on the request path.
+    pub fn reserve_important_headers(&self, count: usize) -> Result<(), WurflError> {
+        let generation = self.data_generation();
+        let pooled = lock(&self.engine.important_header_pool).iter().filter(|(g, _)| *g == generation).count();
+        // created without holding the lock, so that the sets in use are not blocked
+        let mut created = Vec::new();
+        let mut result = Ok(());
+        for _ in pooled..count {
+            match create_important_header(self) {
+                Ok(cih) => created.push(cih),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        // the objects created before a failure are pooled too
+        lock(&self.engine.important_header_pool).extend(created.into_iter().map(|cih| (generation, cih)));
+        result
+    }
+
+    /// Retrieves device data based on a set of important headers, which can be reused for other lookups
+    pub fn lookup_with_important_headers(&self, headers: &ImportantHeaders<'_>) -> Result<Device<'_>, WurflError> {
+        self.check_important_headers_engine(headers)?;
+        let d_handle = unsafe { wurfl_lookup_with_important_header(self.wurfl, headers.handle) };
+        self.wrap_device_handle(d_handle)
+    }
+
+    /// Retrieves device data based on a WURFL device ID and a set of important headers, which can be reused for other
+    /// lookups
+    pub fn lookup_device_id_with_important_headers(&self, device_id: &str, headers: &ImportantHeaders<'_>) -> Result<Device<'_>, WurflError> {
+        self.check_important_headers_engine(headers)?;
+        let c_dev_id = match CString::new(device_id) {
+            Ok(did) => did,
+            Err(_) => {
+                let msg = format!("Unable to convert device id  {}! into a CString", device_id);
+                return Err(WurflError::new(WurflErrorKind::InvalidString, msg));
+            }
+        };
+        let d_handle = unsafe { wurfl_get_device_with_important_header(self.wurfl, c_dev_id.as_ptr(), headers.handle) };
+        self.wrap_device_handle(d_handle)
+    }
+
+    /// Important header objects hold engine specific data, so they cannot be used with another engine, nor with the
+    /// same engine once its data has been reloaded in place
+    fn check_important_headers_engine(&self, headers: &ImportantHeaders<'_>) -> Result<(), WurflError> {
+        if !Arc::ptr_eq(&headers.wurfl.engine, &self.engine) {
+            let msg = "Important headers were created by another WURFL engine".to_string();
+            return Err(WurflError::new(WurflErrorKind::InvalidParameter, msg));
+        }
+        if headers.generation != self.data_generation() {
+            let msg = "Important headers were created before the WURFL data was reloaded, clear them first".to_string();
+            return Err(WurflError::new(WurflErrorKind::InvalidParameter, msg));
+        }
+        Ok(())
+    }
+
+    fn data_generation(&self) -> u64 {
+        self.engine.data_generation.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn wrap_device_handle(&self, d_handle: wurfl_device_handle) -> Result<Device<'_>, WurflError> {
+        if d_handle.is_null() {
+            return Err(WurflError::from_handle(self.wurfl));
+        }
+        Ok(Device {
+            engine: Cow::Borrowed(&self.engine),
+            device: d_handle,
+        })
+    }
+}
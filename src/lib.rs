@@ -3,6 +3,20 @@
 #![allow(non_snake_case)]
 
 use wurfl_sys::*;
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, RwLock};
 include!("./wurfl.rs");
-include!("./device.rs");
\ No newline at end of file
+include!("./device.rs");
+include!("./builder.rs");
+include!("./logging.rs");
+include!("./updater.rs");
+include!("./headers.rs");
+include!("./client_hints.rs");
+include!("./reload.rs");
+include!("./shared.rs");
+#[cfg(feature = "watch")]
+include!("./watch.rs");
@@ -0,0 +1,27 @@
+/// Source of the identifiers that tell apart the log events of different WURFL engines running in the same process
+static NEXT_ENGINE_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+fn next_engine_id() -> u64 {
+    NEXT_ENGINE_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Installs the callback that forwards the engine log lines as `tracing` events.
+/// The engine id is passed to libwurfl as the callback context, so no memory has to outlive the handle.
+#[cfg(feature = "tracing")]
+fn install_engine_log_trampoline(wh: wurfl_handle, engine_id: u64) -> wurfl_error {
+    let context = engine_id as usize as *mut std::os::raw::c_void;
+    unsafe { wurfl_set_log_function(wh, Some(engine_log_trampoline), context) }
+}
+
+/// Called by libwurfl for every engine log line. Panics (ie: raised by a tracing subscriber) are ignored.
+#[cfg(feature = "tracing")]
+unsafe extern "C" fn engine_log_trampoline(log_message: *const c_char, context: *mut std::os::raw::c_void) {
+    if log_message.is_null() {
+        return;
+    }
+    let engine_id = context as usize as u64;
+    catch_ffi_panic(|| {
+        let msg = CStr::from_ptr(log_message).to_string_lossy();
+        tracing::info!(target: "wurfl::engine", engine_id, "{}", msg.trim_end());
+    });
+}
@@ -0,0 +1,72 @@
+/// Describes the WURFL data loaded by a successful reload
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReloadInfo {
+    /// Information about the loaded WURFL file, as returned by `Wurfl::get_info`
+    pub info: String,
+    /// The time of the reload, as returned by `Wurfl::get_last_load_time`
+    pub last_load_time: String,
+    /// Set when the important header names of the new data could not be read. The reload has still succeeded, but
+    /// `Wurfl::important_header_names` keeps returning the previous names.
+    pub important_header_error: Option<String>,
+}
+
+impl Wurfl {
+    /// Reloads the engine in place with a new root WURFL file. The file is validated before it replaces the loaded
+    /// data: when it cannot be loaded, the engine keeps working with the previous data. Lookups running during the
+    /// reload are not blocked. The important header names are read again from the new data, and the `ImportantHeaders`
+    /// sets filled before the reload must be cleared before they are used again.
+    pub fn reload_from_path(&self, path: &str) -> Result<ReloadInfo, WurflError> {
+        let mut invalid = Vec::new();
+        check_file_path("root", path, &mut invalid);
+        if !invalid.is_empty() {
+            return Err(WurflError::from_invalid_settings(invalid));
+        }
+
+        let c_path = to_c_string(path);
+        if unsafe { wurfl_updater_reload_root(self.wurfl, c_path.as_ptr()) } != WURFL_OK {
+            return Err(WurflError::from_handle(self.wurfl));
+        }
+        self.set_builder_root(Some(path.to_string()));
+        Ok(self.data_reloaded())
+    }
+
+    /// Reloads the engine in place with WURFL data held in memory, ie: downloaded from an object store. As with
+    /// `reload_from_path`, the engine keeps working with the previous data when the new data cannot be loaded.
+    /// An engine loaded with patches must be built with `WurflBuilder::enable_reload_from_memory`, so that the patches
+    /// are applied to the new data too.
+    pub fn reload_from_bytes(&self, data: &[u8], format: DataFormat) -> Result<ReloadInfo, WurflError> {
+        if data.is_empty() {
+            return Err(WurflError::new(WurflErrorKind::InvalidParameter, "WURFL data is empty".to_string()));
+        }
+        let we = unsafe {
+            wurfl_updater_reload_root_from_memory(self.wurfl, data.as_ptr() as *const c_char, data.len() as size_t, to_data_format(format))
+        };
+        if we != WURFL_OK {
+            return Err(WurflError::from_handle(self.wurfl));
+        }
+        // the root file does not hold the loaded data anymore: building from it would roll the data back
+        self.set_builder_root(None);
+        Ok(self.data_reloaded())
+    }
+
+    /// Keeps the root of the stored settings in line with the data loaded in place
+    fn set_builder_root(&self, root: Option<String>) {
+        write_lock(&self.builder).root = root;
+    }
+
+    /// Updates the engine state that depends on the loaded data, once new data has been loaded in place. The new data
+    /// is in use whatever happens here, so a failure is reported in the returned ReloadInfo instead of as an error.
+    fn data_reloaded(&self) -> ReloadInfo {
+        self.engine.data_generation.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let important_header_error = self.refresh_important_header_table().err().map(|e| e.to_string());
+        ReloadInfo { important_header_error, ..self.reload_info() }
+    }
+
+    fn reload_info(&self) -> ReloadInfo {
+        ReloadInfo {
+            info: self.get_info(),
+            last_load_time: self.get_last_load_time(),
+            important_header_error: None,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+/// SharedWurfl holds the WURFL engine used by a long-running server, and lets a new engine replace it at any time.
+///
+/// Lookups are run on the engine returned by `load`, which stays alive as long as it is referenced: swapping in a new
+/// engine does not block or invalidate the lookups already in flight, nor the `Device`s they returned.
+/// Callbacks registered on an engine (ie: `Wurfl::set_updater_log_callback`) are not carried over to its replacement.
+/// An `UpdaterHandle` keeps its engine alive: a swapped-out engine keeps running its updater thread, and checking
+/// for new data, until the handle is stopped or dropped.
+pub struct SharedWurfl {
+    current: RwLock<Arc<Wurfl>>,
+}
+
+impl SharedWurfl {
+    /// Creates a SharedWurfl holding the given engine
+    pub fn new(wurfl: Wurfl) -> SharedWurfl {
+        SharedWurfl { current: RwLock::new(Arc::new(wurfl)) }
+    }
+
+    /// Returns the current engine
+    pub fn load(&self) -> Arc<Wurfl> {
+        Arc::clone(&read_lock(&self.current))
+    }
+
+    /// Replaces the current engine and returns the previous one
+    pub fn swap(&self, wurfl: Wurfl) -> Arc<Wurfl> {
+        let new_engine = Arc::new(wurfl);
+        std::mem::replace(&mut *write_lock(&self.current), new_engine)
+    }
+
+    /// Builds a new engine with the given settings, then swaps it in and returns the previous engine.
+    /// Lookups keep using the current engine while the new one loads; when loading fails, the current engine is kept.
+    pub fn rebuild(&self, builder: &WurflBuilder) -> Result<Arc<Wurfl>, WurflError> {
+        let wurfl = builder.build()?;
+        Ok(self.swap(wurfl))
+    }
+
+    /// Builds a new engine with the settings of the current one and a new root WURFL file, then swaps it in and
+    /// returns the previous engine
+    pub fn rebuild_from_path(&self, path: &str) -> Result<Arc<Wurfl>, WurflError> {
+        let builder = self.load().get_builder().root(path);
+        self.rebuild(&builder)
+    }
+
+    /// Runs `rebuild` on a new thread. The returned handle reports whether the new engine has been swapped in.
+    pub fn rebuild_in_background(self: &Arc<Self>, builder: WurflBuilder) -> std::thread::JoinHandle<Result<(), WurflError>> {
+        let shared = Arc::clone(self);
+        std::thread::spawn(move || shared.rebuild(&builder).map(|_| ()))
+    }
+}
+
+impl From<Wurfl> for SharedWurfl {
+    fn from(wurfl: Wurfl) -> SharedWurfl {
+        SharedWurfl::new(wurfl)
+    }
+}
@@ -0,0 +1,617 @@
+/// Events of the WURFL updater process, parsed from the updater log lines
+#[non_exhaustive]
+#[derive(Clone, PartialEq, Debug)]
+pub enum UpdaterEvent {
+    /// The updater started checking for a new WURFL file. Starting the updater thread is not a check.
+    CheckStarted,
+    /// The WURFL file on the server has not changed since the last download
+    NotModified,
+    /// A new WURFL file has been downloaded
+    Downloaded,
+    /// The engine has been reloaded with the new WURFL file
+    Reloaded,
+    /// The update failed, holds the updater log line
+    Failed(String),
+    /// An updater log line that does not match any other event
+    Other(String),
+}
+
+impl UpdaterEvent {
+    /// Parses an updater log line into an event. The line is matched on whole words, so that numbers (ie: a size of
+    /// 1304512 bytes) and negated words (ie: "no error") do not change the event.
+    pub fn parse(log_message: &str) -> UpdaterEvent {
+        let msg = log_message.trim();
+        let lc_msg = msg.to_lowercase();
+        let words: Vec<&str> = lc_msg.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+        let has_word = |candidates: &[&str]| words.iter().any(|w| candidates.contains(w));
+        let has_phrase = |phrase: &[&str]| words.windows(phrase.len()).any(|w| w == phrase);
+        // a word counts only when it is not preceded by one of the negations
+        let has_unnegated_word = |candidates: &[&str]| {
+            words.iter().enumerate().any(|(i, w)| {
+                candidates.contains(w) && (i == 0 || !["no", "not", "without", "0", "zero"].contains(&words[i - 1]))
+            })
+        };
+
+        if has_unnegated_word(&["error", "errors", "fail", "failed", "failure", "unable", "cannot", "timeout"])
+            || has_phrase(&["timed", "out"]) {
+            UpdaterEvent::Failed(msg.to_string())
+        } else if has_phrase(&["not", "modified"]) || has_phrase(&["up", "to", "date"]) || has_phrase(&["no", "new"])
+            || ["http", "status", "code", "response"].iter().any(|w| has_phrase(&[w, "304"])) {
+            UpdaterEvent::NotModified
+        } else if has_word(&["reload", "reloaded", "reloading"]) {
+            UpdaterEvent::Reloaded
+        } else if has_word(&["download", "downloaded", "downloading"]) {
+            UpdaterEvent::Downloaded
+        } else if has_word(&["check", "checking"]) {
+            UpdaterEvent::CheckStarted
+        } else {
+            UpdaterEvent::Other(msg.to_string())
+        }
+    }
+}
+
+type UpdaterLogCallback = Arc<dyn Fn(&str) + Send + Sync>;
+type UpdaterEventCallback = Arc<dyn Fn(UpdaterEvent) + Send + Sync>;
+
+/// Dispatches the updater log lines to the registered callbacks and to the updater log file
+#[derive(Default)]
+struct UpdaterLogSink {
+    log_callback: Mutex<Option<UpdaterLogCallback>>,
+    event_callback: Mutex<Option<UpdaterEventCallback>>,
+    // the updater log file, written only by the sink: libwurfl is never given its path, so that no line is written twice
+    log_path: Mutex<Option<String>>,
+}
+
+/// Keeps track of the updater executions, of their activity and of the settings passed to libwurfl, which has no
+/// getter for them
+#[derive(Default)]
+struct UpdaterControl {
+    run: Mutex<UpdaterRun>,
+    state: Mutex<UpdaterState>,
+    settings: Mutex<UpdaterSettings>,
+}
+
+/// The updater settings last passed to libwurfl, None while a setting has its libwurfl default
+#[derive(Clone, Default)]
+struct UpdaterSettings {
+    data_url: Option<String>,
+    frequency: Option<WurflUpdaterFrequency>,
+    // the connection and data transfer timeouts
+    timeouts: Option<(i32, i32)>,
+}
+
+/// Tracks the updater executions, so that an UpdaterHandle only stops the execution it started
+#[derive(Default)]
+struct UpdaterRun {
+    // the generation of the running execution, None when the updater is not running
+    current: Option<u64>,
+    // the number of executions started so far
+    starts: u64,
+}
+
+/// Updater activity, as parsed from the updater log lines
+#[derive(Default)]
+struct UpdaterState {
+    last_check_time: Option<std::time::SystemTime>,
+    last_error: Option<String>,
+}
+
+impl UpdaterLogSink {
+    fn with_log_path(log_path: Option<String>) -> UpdaterLogSink {
+        UpdaterLogSink { log_path: Mutex::new(log_path), ..UpdaterLogSink::default() }
+    }
+
+    fn set_log_path(&self, log_path: &str) {
+        *lock(&self.log_path) = Some(log_path.to_string());
+    }
+
+    fn dispatch(&self, log_message: &str, event: UpdaterEvent) {
+        self.write_log_file(log_message);
+
+        // callbacks are cloned out of the locks, so that they can register other callbacks without deadlocking
+        let log_callback = lock(&self.log_callback).clone();
+        if let Some(cb) = log_callback {
+            cb(log_message);
+        }
+        let event_callback = lock(&self.event_callback).clone();
+        if let Some(cb) = event_callback {
+            cb(event);
+        }
+    }
+
+    fn write_log_file(&self, log_message: &str) {
+        let log_path = lock(&self.log_path).clone();
+        if let Some(path) = log_path {
+            // logging failures are ignored, as libwurfl does
+            let _ = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut f| std::io::Write::write_all(&mut f, format!("{}\n", log_message.trim_end()).as_bytes()));
+        }
+    }
+}
+
+impl UpdaterControl {
+    fn record(&self, event: &UpdaterEvent) {
+        let mut state = lock(&self.state);
+        match event {
+            UpdaterEvent::Other(_) => {}
+            UpdaterEvent::Failed(msg) => {
+                state.last_check_time = Some(std::time::SystemTime::now());
+                state.last_error = Some(msg.clone());
+            }
+            _ => state.last_check_time = Some(std::time::SystemTime::now()),
+        }
+    }
+}
+
+/// Called by libwurfl for every updater log line, with the engine handle as data. Panics raised by the callbacks are
+/// ignored.
+unsafe extern "C" fn updater_log_trampoline(log_message: *const c_char, data: *mut std::os::raw::c_void) {
+    if log_message.is_null() || data.is_null() {
+        return;
+    }
+    let engine = &*(data as *const EngineHandle);
+    catch_ffi_panic(|| {
+        let msg = CStr::from_ptr(log_message).to_string_lossy();
+        let event = UpdaterEvent::parse(&msg);
+        engine.updater.record(&event);
+        engine.updater_log_sink.dispatch(&msg, event);
+    });
+}
+
+impl Wurfl {
+    /// Registers a callback that receives every updater log line. The lines are still written to the updater log
+    /// file, when one is set.
+    pub fn set_updater_log_callback<F>(&self, callback: F) -> Result<(), WurflError> where
+        F: Fn(&str) + Send + Sync + 'static {
+        *lock(&self.engine.updater_log_sink.log_callback) = Some(Arc::new(callback));
+        self.install_updater_log_trampoline()
+    }
+
+    /// Registers a callback that receives the events parsed from every updater log line. The lines are still written
+    /// to the updater log file, when one is set.
+    pub fn set_updater_event_callback<F>(&self, callback: F) -> Result<(), WurflError> where
+        F: Fn(UpdaterEvent) + Send + Sync + 'static {
+        *lock(&self.engine.updater_log_sink.event_callback) = Some(Arc::new(callback));
+        self.install_updater_log_trampoline()
+    }
+
+    /// Starts the periodic updater execution. The updater runs until the returned handle is stopped or dropped, which
+    /// also keeps the engine alive. Fails with `WurflErrorKind::UpdaterAlreadyRunning` if the updater is already running.
+    pub fn updater_start(&self) -> Result<UpdaterHandle, WurflError> {
+        let generation = self.start_updater_run()?;
+        Ok(UpdaterHandle { engine: Arc::clone(&self.engine), generation })
+    }
+
+    /// Stops the periodic updater execution, whether or not it was started by this engine's `UpdaterHandle`
+    pub fn updater_stop(&self) -> Result<UpdaterStopOutcome, WurflError> {
+        stop_updater_run(&self.engine, None)
+    }
+
+    /// Starts the updater and returns the generation of the new execution
+    fn start_updater_run(&self) -> Result<u64, WurflError> {
+        // the run lock is held until the updater is started, so that concurrent starts cannot both succeed
+        let mut run = lock(&self.engine.updater.run);
+        if run.current.is_some() {
+            let msg = "The WURFL updater is already running".to_string();
+            return Err(WurflError::new(WurflErrorKind::UpdaterAlreadyRunning, msg));
+        }
+        // the log lines are needed to report the updater status
+        self.install_updater_log_trampoline()?;
+        if unsafe { wurfl_updater_start(self.wurfl) } != WURFL_OK {
+            return Err(WurflError::from_handle(self.wurfl));
+        }
+        run.starts += 1;
+        run.current = Some(run.starts);
+        Ok(run.starts)
+    }
+
+    fn install_updater_log_trampoline(&self) -> Result<(), WurflError> {
+        // the engine handle outlives the libwurfl handle, so the pointer stays valid as long as libwurfl keeps it
+        let data = &*self.engine as *const EngineHandle as *mut std::os::raw::c_void;
+        if unsafe { wurfl_updater_set_log_function(self.wurfl, Some(updater_log_trampoline), data) } != WURFL_OK {
+            return Err(WurflError::from_handle(self.wurfl));
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of an updater stop request
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UpdaterStopOutcome {
+    /// The updater was running and has been stopped
+    Stopped,
+    /// The updater was not running
+    NotRunning,
+}
+
+/// Status of the periodic updater execution, returned by `UpdaterHandle::status`
+#[derive(Clone, PartialEq, Debug)]
+pub struct UpdaterStatus {
+    /// True until the updater is stopped
+    pub running: bool,
+    /// The time of the last update check, None if no check has been logged yet
+    pub last_check_time: Option<std::time::SystemTime>,
+    /// The log line of the last failed update, if any
+    pub last_error: Option<String>,
+    /// Information about the loaded WURFL data, as returned by `Wurfl::get_info`
+    pub data_version: String,
+    /// The time the loaded WURFL data was loaded, as returned by `Wurfl::get_last_load_time`
+    pub last_load_time: String,
+}
+
+/// UpdaterHandle controls the periodic updater execution started by `Wurfl::updater_start`.
+/// The updater is stopped when the handle is dropped; until then the handle keeps the engine alive, so an engine
+/// swapped out of a `SharedWurfl` keeps running its updater thread as long as its handle is held.
+/// A handle only controls the execution it started: once the updater has been stopped and started again, the
+/// previous handle reports it is not running and dropping it leaves the new execution running.
+pub struct UpdaterHandle {
+    engine: Arc<EngineHandle>,
+    // the execution started with this handle, generations start at 1 and 0 marks a handle already stopped
+    generation: u64,
+}
+
+impl UpdaterHandle {
+    /// Returns true until the updater execution started with this handle is stopped
+    pub fn is_running(&self) -> bool {
+        lock(&self.engine.updater.run).current == Some(self.generation)
+    }
+
+    /// Returns the current status of the updater
+    pub fn status(&self) -> UpdaterStatus {
+        let (last_check_time, last_error) = {
+            let state = lock(&self.engine.updater.state);
+            (state.last_check_time, state.last_error.clone())
+        };
+        UpdaterStatus {
+            running: self.is_running(),
+            last_check_time,
+            last_error,
+            data_version: to_owned_string(unsafe { wurfl_get_wurfl_info(self.engine.wurfl) }),
+            last_load_time: to_owned_string(unsafe { wurfl_get_last_load_time_as_string(self.engine.wurfl) }),
+        }
+    }
+
+    /// Stops the updater, reporting whether it was still running
+    pub fn stop(mut self) -> Result<UpdaterStopOutcome, WurflError> {
+        let outcome = stop_updater_run(&self.engine, Some(self.generation));
+        // the execution is stopped or failed to stop: either way, dropping the handle must not try again
+        self.generation = 0;
+        outcome
+    }
+}
+
+impl Drop for UpdaterHandle {
+    fn drop(&mut self) {
+        if self.generation != 0 {
+            let _ = stop_updater_run(&self.engine, Some(self.generation));
+        }
+    }
+}
+
+/// Stops the updater execution of the given generation, or any execution when None
+fn stop_updater_run(engine: &EngineHandle, generation: Option<u64>) -> Result<UpdaterStopOutcome, WurflError> {
+    // the execution is marked as stopped before libwurfl waits for the updater thread, which may be calling back
+    // into is_running or status: the run lock must not be held meanwhile
+    let stopped = {
+        let mut run = lock(&engine.updater.run);
+        if generation.is_some() && run.current != generation {
+            return Ok(UpdaterStopOutcome::NotRunning);
+        }
+        run.current.take()
+    };
+    let we = unsafe { wurfl_updater_stop(engine.wurfl) };
+    if we == wurfl_error_WURFL_ERROR_UPDATER_NOT_RUNNING {
+        return Ok(UpdaterStopOutcome::NotRunning);
+    }
+    if we != WURFL_OK {
+        // the updater is still running, unless it has been started again meanwhile
+        let mut run = lock(&engine.updater.run);
+        if run.current.is_none() {
+            run.current = stopped;
+        }
+        return Err(WurflError::from_handle(engine.wurfl));
+    }
+    Ok(UpdaterStopOutcome::Stopped)
+}
+
+/// Shorter name of `WurflUpdaterFrequency`, used by `UpdaterConfig`
+pub type UpdaterFrequency = WurflUpdaterFrequency;
+
+/// A URL of WURFL data for the updater. Only its scheme and host are checked, libwurfl may still reject it when
+/// `Wurfl::configure_updater` sets it
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct UpdaterDataUrl {
+    url: String,
+}
+
+impl UpdaterDataUrl {
+    /// Checks that the URL is an http or https URL with a host. Fails with `WurflErrorKind::UpdaterInvalidDataUrl`
+    pub fn parse(url: &str) -> Result<UpdaterDataUrl, WurflError> {
+        let invalid = |reason: &str| {
+            let msg = format!("Invalid updater data URL {:?}: {}", url, reason);
+            Err(WurflError::new(WurflErrorKind::UpdaterInvalidDataUrl, msg))
+        };
+        if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return invalid("it contains whitespace or control characters");
+        }
+        let lc_url = url.to_ascii_lowercase();
+        let rest = match lc_url.strip_prefix("https://").or_else(|| lc_url.strip_prefix("http://")) {
+            Some(r) => r,
+            None => return invalid("only http and https URLs are supported"),
+        };
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+        let host = authority.rsplit('@').next().unwrap_or("");
+        if host.is_empty() || host.starts_with(':') {
+            return invalid("the host is missing");
+        }
+        Ok(UpdaterDataUrl { url: url.to_string() })
+    }
+
+    /// Returns the URL as passed to `parse`
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+}
+
+impl std::str::FromStr for UpdaterDataUrl {
+    type Err = WurflError;
+
+    fn from_str(url: &str) -> Result<UpdaterDataUrl, WurflError> {
+        UpdaterDataUrl::parse(url)
+    }
+}
+
+impl std::fmt::Display for UpdaterDataUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+/// Timeout of the updater http calls
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Timeout {
+    /// The libwurfl default timeout
+    Default,
+    /// No timeout
+    Infinite,
+    /// Times out after the given duration, rounded up to milliseconds. It must not be zero, and less than about
+    /// 24 days.
+    After(std::time::Duration),
+}
+
+/// UpdaterConfig collects the updater settings applied by `Wurfl::configure_updater`.
+/// Settings that are not set keep their current value.
+#[derive(Clone, Default)]
+pub struct UpdaterConfig {
+    data_url: Option<UpdaterDataUrl>,
+    frequency: Option<UpdaterFrequency>,
+    connection_timeout: Option<Timeout>,
+    data_transfer_timeout: Option<Timeout>,
+    log_path: Option<String>,
+    log_callback: Option<UpdaterLogCallback>,
+    event_callback: Option<UpdaterEventCallback>,
+}
+
+impl UpdaterConfig {
+    /// Creates a configuration that changes no setting
+    pub fn new() -> UpdaterConfig {
+        UpdaterConfig::default()
+    }
+
+    /// Sets the URL of the WURFL file to download
+    pub fn data_url(mut self, data_url: UpdaterDataUrl) -> UpdaterConfig {
+        self.data_url = Some(data_url);
+        self
+    }
+
+    /// Sets the interval of update checks
+    pub fn frequency(mut self, frequency: UpdaterFrequency) -> UpdaterConfig {
+        self.frequency = Some(frequency);
+        self
+    }
+
+    /// Sets the connection timeout of the updater http calls, the data transfer timeout keeps its current value
+    pub fn connection_timeout(mut self, timeout: Timeout) -> UpdaterConfig {
+        self.connection_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the data transfer timeout of the updater http calls, the connection timeout keeps its current value
+    pub fn data_transfer_timeout(mut self, timeout: Timeout) -> UpdaterConfig {
+        self.data_transfer_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the path of the updater log file
+    pub fn log_path(mut self, log_file: &str) -> UpdaterConfig {
+        self.log_path = Some(log_file.to_string());
+        self
+    }
+
+    /// Sets a callback that receives every updater log line
+    pub fn log_callback<F>(mut self, callback: F) -> UpdaterConfig where
+        F: Fn(&str) + Send + Sync + 'static {
+        self.log_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets a callback that receives the events parsed from every updater log line
+    pub fn event_callback<F>(mut self, callback: F) -> UpdaterConfig where
+        F: Fn(UpdaterEvent) + Send + Sync + 'static {
+        self.event_callback = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl std::fmt::Debug for UpdaterConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UpdaterConfig")
+            .field("data_url", &self.data_url)
+            .field("frequency", &self.frequency)
+            .field("connection_timeout", &self.connection_timeout)
+            .field("data_transfer_timeout", &self.data_transfer_timeout)
+            .field("log_path", &self.log_path)
+            .field("log_callback", &self.log_callback.is_some())
+            .field("event_callback", &self.event_callback.is_some())
+            .finish()
+    }
+}
+
+impl Wurfl {
+    /// Applies an updater configuration. Every setting is checked before any of them is passed to libwurfl: when one
+    /// or more of them are invalid, nothing is changed and the returned error lists them in `invalid_settings`.
+    /// libwurfl may still reject a checked setting, ie: a data URL it cannot use: the settings applied before it are
+    /// then restored to their previous values, and the error is returned. libwurfl cannot clear a data URL or a
+    /// frequency, so one applied while none had been set on the engine before is kept.
+    pub fn configure_updater(&self, config: &UpdaterConfig) -> Result<(), WurflError> {
+        let mut invalid = Vec::new();
+        let connection_timeout = config.connection_timeout.map(|t| to_timeout_ms("connection_timeout", t, &mut invalid));
+        let data_transfer_timeout = config.data_transfer_timeout.map(|t| to_timeout_ms("data_transfer_timeout", t, &mut invalid));
+        if let Some(log_path) = &config.log_path {
+            check_log_path("log_path", log_path, &mut invalid);
+        }
+        if !invalid.is_empty() {
+            return Err(WurflError::from_invalid_updater_settings(invalid));
+        }
+
+        let previous_settings = lock(&self.engine.updater.settings).clone();
+        let previous_log_path = lock(&self.engine.updater_log_sink.log_path).clone();
+        let applied = self.apply_updater_config(config, connection_timeout, data_transfer_timeout);
+        if applied.is_err() {
+            self.restore_updater_settings(previous_settings, previous_log_path);
+        }
+        applied
+    }
+
+    fn apply_updater_config(&self, config: &UpdaterConfig, connection_timeout: Option<i32>,
+                            data_transfer_timeout: Option<i32>) -> Result<(), WurflError> {
+        if let Some(data_url) = &config.data_url {
+            self.updater_set_data_url(data_url.as_str())?;
+        }
+        if let Some(frequency) = config.frequency {
+            self.updater_set_data_frequency(frequency)?;
+        }
+        if connection_timeout.is_some() || data_transfer_timeout.is_some() {
+            // libwurfl sets both timeouts at once, the one that is not configured is passed again with its current value
+            let current = lock(&self.engine.updater.settings).timeouts.unwrap_or((-1, -1));
+            self.updater_set_data_url_timeouts(connection_timeout.unwrap_or(current.0), data_transfer_timeout.unwrap_or(current.1))?;
+        }
+        if let Some(log_path) = &config.log_path {
+            self.updater_set_log_path(log_path)?;
+        }
+        if config.log_callback.is_some() || config.event_callback.is_some() {
+            // installed first, so that the callbacks are only set once nothing can fail anymore
+            self.install_updater_log_trampoline()?;
+        }
+        let sink = &self.engine.updater_log_sink;
+        if let Some(callback) = &config.log_callback {
+            *lock(&sink.log_callback) = Some(Arc::clone(callback));
+        }
+        if let Some(callback) = &config.event_callback {
+            *lock(&sink.event_callback) = Some(Arc::clone(callback));
+        }
+        Ok(())
+    }
+
+    /// Passes the previous updater settings to libwurfl again after a configuration failed part way. Errors are
+    /// ignored: the settings being restored were accepted by libwurfl before.
+    fn restore_updater_settings(&self, previous: UpdaterSettings, previous_log_path: Option<String>) {
+        let current = lock(&self.engine.updater.settings).clone();
+        if previous.data_url != current.data_url {
+            if let Some(data_url) = &previous.data_url {
+                let _ = self.updater_set_data_url(data_url);
+            }
+        }
+        if previous.frequency != current.frequency {
+            if let Some(frequency) = previous.frequency {
+                let _ = self.updater_set_data_frequency(frequency);
+            }
+        }
+        if previous.timeouts != current.timeouts {
+            let (conn_timeout, data_transfer_timeout) = previous.timeouts.unwrap_or((-1, -1));
+            let _ = self.updater_set_data_url_timeouts(conn_timeout, data_transfer_timeout);
+            lock(&self.engine.updater.settings).timeouts = previous.timeouts;
+        }
+        *lock(&self.engine.updater_log_sink.log_path) = previous_log_path;
+    }
+}
+
+/// Converts an updater timeout to the milliseconds expected by libwurfl, where -1 selects the default timeout and 0
+/// disables the timeout
+fn to_timeout_ms(setting: &'static str, timeout: Timeout, invalid: &mut Vec<InvalidSetting>) -> i32 {
+    let timeout = match timeout {
+        Timeout::Default => return -1,
+        Timeout::Infinite => return 0,
+        Timeout::After(t) => t,
+    };
+    if timeout.is_zero() {
+        invalid.push(invalid_setting(setting, WurflErrorKind::InvalidParameter, "timeout is zero, use Timeout::Infinite for no timeout".to_string()));
+        return -1;
+    }
+    // rounded up, so that sub-millisecond timeouts do not become 0 (no timeout)
+    let ms = timeout.as_nanos().div_ceil(1_000_000);
+    match <i32 as std::convert::TryFrom<u128>>::try_from(ms) {
+        Ok(ms) => ms,
+        Err(_) => {
+            invalid.push(invalid_setting(setting, WurflErrorKind::InvalidParameter, format!("timeout {:?} is too long", timeout)));
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod updater_event_tests {
+    // the lines below are written for these tests: no libwurfl 1.12 updater output has been captured for them yet
+    use super::*;
+
+    #[test]
+    fn parses_check_started() {
+        assert_eq!(UpdaterEvent::parse("WURFL updater: checking for a new WURFL file"), UpdaterEvent::CheckStarted);
+    }
+
+    #[test]
+    fn starting_the_updater_is_not_a_check() {
+        let line = "Updater thread started";
+        assert_eq!(UpdaterEvent::parse(line), UpdaterEvent::Other(line.to_string()));
+        let line = "Starting WURFL updater";
+        assert_eq!(UpdaterEvent::parse(line), UpdaterEvent::Other(line.to_string()));
+    }
+
+    #[test]
+    fn parses_not_modified() {
+        assert_eq!(UpdaterEvent::parse("Server answered HTTP 304, file not modified"), UpdaterEvent::NotModified);
+        assert_eq!(UpdaterEvent::parse("WURFL file is up to date"), UpdaterEvent::NotModified);
+        assert_eq!(UpdaterEvent::parse("No new WURFL file available"), UpdaterEvent::NotModified);
+    }
+
+    #[test]
+    fn numbers_are_not_status_codes() {
+        assert_eq!(UpdaterEvent::parse("Downloaded 1304512 bytes of WURFL data"), UpdaterEvent::Downloaded);
+        assert_eq!(UpdaterEvent::parse("Downloaded 304 bytes of WURFL data"), UpdaterEvent::Downloaded);
+    }
+
+    #[test]
+    fn negated_errors_are_not_failures() {
+        assert_eq!(UpdaterEvent::parse("WURFL updater: no error, file downloaded"), UpdaterEvent::Downloaded);
+        assert_eq!(UpdaterEvent::parse("Engine reloaded without errors"), UpdaterEvent::Reloaded);
+    }
+
+    #[test]
+    fn parses_failures() {
+        let line = "Error downloading https://data.scientiamobile.com/xxxxx/wurfl.zip: timed out";
+        assert_eq!(UpdaterEvent::parse(line), UpdaterEvent::Failed(line.to_string()));
+        let line = "Unable to reload the WURFL engine";
+        assert_eq!(UpdaterEvent::parse(line), UpdaterEvent::Failed(line.to_string()));
+        let line = "Download failed: HTTP 429 Too Many Requests";
+        assert_eq!(UpdaterEvent::parse(line), UpdaterEvent::Failed(line.to_string()));
+    }
+
+    #[test]
+    fn parses_reloaded_and_other() {
+        assert_eq!(UpdaterEvent::parse("WURFL engine reloaded with the new data file\n"), UpdaterEvent::Reloaded);
+        assert_eq!(UpdaterEvent::parse("  license expires on 2027-01-01 "), UpdaterEvent::Other("license expires on 2027-01-01".to_string()));
+    }
+}
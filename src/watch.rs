@@ -0,0 +1,270 @@
+/// Outcome of a reload triggered by `WurflWatcher`
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// The changed files have been loaded into a new engine, which has been swapped in
+    Reloaded {
+        changed_files: Vec<String>,
+        reload_info: ReloadInfo,
+    },
+    /// The changed files could not be loaded, the previous engine is still in use.
+    /// The reload is attempted again when the files change once more.
+    Failed {
+        changed_files: Vec<String>,
+        error: WurflError,
+    },
+}
+
+/// Timings of the WurflWatcher file checks
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WatchOptions {
+    /// How often the watched files are checked
+    pub poll_interval: std::time::Duration,
+    /// How long the changed files must stay unchanged before the reload, so that files still being written are not loaded
+    pub debounce: std::time::Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> WatchOptions {
+        WatchOptions {
+            poll_interval: std::time::Duration::from_secs(1),
+            debounce: std::time::Duration::from_secs(2),
+        }
+    }
+}
+
+/// Size and modification time of a watched file, None when the file is missing
+type FileStamp = Option<(u64, std::time::SystemTime)>;
+
+/// WurflWatcher monitors the root and patch files of the engine held by a `SharedWurfl`, and swaps in a new engine
+/// loaded from them whenever they change. The watched files and the settings of the new engine are taken from the
+/// engine in use at every check (`Wurfl::get_builder`), so the watcher follows the engines swapped in or reloaded
+/// from another path. While the engine in use has been loaded from memory there is no file to watch, and the
+/// watcher waits for an engine loaded from a file. The watcher stops when dropped.
+pub struct WurflWatcher {
+    stop: Arc<(Mutex<bool>, std::sync::Condvar)>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl WurflWatcher {
+    /// Starts watching the files of the current engine with the default options. `callback` receives the outcome of
+    /// every reload, and is called from the watcher thread.
+    pub fn start<F>(shared: Arc<SharedWurfl>, callback: F) -> Result<WurflWatcher, WurflError> where
+        F: Fn(WatchEvent) + Send + 'static {
+        WurflWatcher::start_with_options(shared, WatchOptions::default(), callback)
+    }
+
+    /// Starts watching the files of the current engine, checking them with the given timings
+    pub fn start_with_options<F>(shared: Arc<SharedWurfl>, options: WatchOptions, callback: F) -> Result<WurflWatcher, WurflError> where
+        F: Fn(WatchEvent) + Send + 'static {
+        if watched_paths(&shared.load().get_builder()).is_none() {
+            let msg = "The engine data was loaded from memory, there is no root file to watch".to_string();
+            return Err(WurflError::new(WurflErrorKind::InvalidParameter, msg));
+        }
+
+        let stop = Arc::new((Mutex::new(false), std::sync::Condvar::new()));
+        let thread_stop = Arc::clone(&stop);
+        let spawned = std::thread::Builder::new()
+            .name("wurfl-watcher".to_string())
+            .spawn(move || watch_files(shared, options, thread_stop, callback));
+        match spawned {
+            Ok(thread) => Ok(WurflWatcher { stop, thread: Some(thread) }),
+            Err(e) => {
+                let msg = format!("Unable to start the WURFL watcher thread: {}", e);
+                Err(WurflError::new(WurflErrorKind::Internal, msg))
+            }
+        }
+    }
+
+    /// Stops watching the files, waiting for a reload in progress to complete
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for WurflWatcher {
+    fn drop(&mut self) {
+        let (stopped, cvar) = &*self.stop;
+        *lock(stopped) = true;
+        cvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns the root and patch files of the engine built by `builder`, None when its data is loaded from memory
+fn watched_paths(builder: &WurflBuilder) -> Option<Vec<String>> {
+    let mut paths = vec![builder.root.clone()?];
+    paths.extend(builder.patches.iter().cloned());
+    Some(paths)
+}
+
+fn stamp_files(paths: &[String]) -> Vec<FileStamp> {
+    paths.iter()
+        .map(|p| std::fs::metadata(p).ok().and_then(|m| Some((m.len(), m.modified().ok()?))))
+        .collect()
+}
+
+/// Tells when changed files have stopped changing and can be loaded
+struct Debounce {
+    debounce: std::time::Duration,
+    /// the files as they were when last loaded, or when a load last failed
+    loaded: Vec<FileStamp>,
+    /// the changed files as seen by the previous check, with the time they were first seen this way
+    pending: Option<(Vec<FileStamp>, std::time::Instant)>,
+}
+
+impl Debounce {
+    fn new(loaded: Vec<FileStamp>, debounce: std::time::Duration) -> Debounce {
+        Debounce { debounce, loaded, pending: None }
+    }
+
+    /// Takes the files as seen by a check made at `now`. Returns the indexes of the changed files once they have
+    /// stayed unchanged for the debounce time: they are then handled as loaded, whatever the outcome of the load.
+    fn check(&mut self, current: Vec<FileStamp>, now: std::time::Instant) -> Option<Vec<usize>> {
+        if current == self.loaded {
+            self.pending = None;
+            return None;
+        }
+        match &self.pending {
+            // a missing file is likely being replaced, wait for it to come back
+            Some((stamps, since)) if *stamps == current && now.duration_since(*since) >= self.debounce
+                && current.iter().all(|s| s.is_some()) => {}
+            Some((stamps, _)) if *stamps == current => return None,
+            // still changing: wait for the files to settle
+            _ => {
+                self.pending = Some((current, now));
+                return None;
+            }
+        }
+        let changed = current.iter().zip(self.loaded.iter())
+            .enumerate()
+            .filter(|(_, (c, l))| c != l)
+            .map(|(i, _)| i)
+            .collect();
+        self.loaded = current;
+        self.pending = None;
+        Some(changed)
+    }
+}
+
+fn watch_files<F: Fn(WatchEvent)>(shared: Arc<SharedWurfl>, options: WatchOptions,
+                                  stop: Arc<(Mutex<bool>, std::sync::Condvar)>, callback: F) {
+    // the files watched by the previous check, along with their debounce state
+    let mut watched = watched_paths(&shared.load().get_builder()).map(|paths| {
+        let loaded = stamp_files(&paths);
+        (paths, Debounce::new(loaded, options.debounce))
+    });
+
+    let (stopped, cvar) = &*stop;
+    loop {
+        let guard = cvar.wait_timeout_while(lock(stopped), options.poll_interval, |s| !*s)
+            .map_or_else(|poisoned| poisoned.into_inner().0, |(g, _)| g);
+        if *guard {
+            return;
+        }
+        drop(guard);
+
+        let builder = shared.load().get_builder();
+        let paths = match watched_paths(&builder) {
+            Some(paths) => paths,
+            None => {
+                watched = None;
+                continue;
+            }
+        };
+        let current = stamp_files(&paths);
+        let (paths, debounce) = match &mut watched {
+            Some((watched_paths, debounce)) if *watched_paths == paths => (watched_paths, debounce),
+            // the engine in use has been replaced by one loaded from other files, which are as they were loaded
+            _ => {
+                watched = Some((paths, Debounce::new(current, options.debounce)));
+                continue;
+            }
+        };
+        let changed = match debounce.check(current, std::time::Instant::now()) {
+            Some(changed) => changed,
+            None => continue,
+        };
+        let changed_files = changed.into_iter().map(|i| paths[i].clone()).collect();
+        let event = match builder.build() {
+            Ok(wurfl) => {
+                let reload_info = wurfl.reload_info();
+                shared.swap(wurfl);
+                WatchEvent::Reloaded { changed_files, reload_info }
+            }
+            Err(error) => WatchEvent::Failed { changed_files, error },
+        };
+        // a panicking callback must not stop the watcher
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| callback(event)));
+    }
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use super::*;
+    use std::time::{Duration, Instant, SystemTime};
+
+    const DEBOUNCE: Duration = Duration::from_secs(2);
+
+    fn stamp(len: u64) -> FileStamp {
+        Some((len, SystemTime::UNIX_EPOCH + Duration::from_secs(len)))
+    }
+
+    #[test]
+    fn unchanged_files_are_not_reloaded() {
+        let start = Instant::now();
+        let mut debounce = Debounce::new(vec![stamp(1), stamp(2)], DEBOUNCE);
+        assert_eq!(debounce.check(vec![stamp(1), stamp(2)], start), None);
+        assert_eq!(debounce.check(vec![stamp(1), stamp(2)], start + DEBOUNCE * 2), None);
+    }
+
+    #[test]
+    fn changed_files_are_reloaded_once_settled() {
+        let start = Instant::now();
+        let mut debounce = Debounce::new(vec![stamp(1), stamp(2)], DEBOUNCE);
+        assert_eq!(debounce.check(vec![stamp(1), stamp(3)], start), None);
+        assert_eq!(debounce.check(vec![stamp(1), stamp(3)], start + DEBOUNCE / 2), None);
+        assert_eq!(debounce.check(vec![stamp(1), stamp(3)], start + DEBOUNCE), Some(vec![1]));
+        // the reloaded files are not reloaded again
+        assert_eq!(debounce.check(vec![stamp(1), stamp(3)], start + DEBOUNCE * 2), None);
+    }
+
+    #[test]
+    fn files_still_changing_restart_the_debounce() {
+        let start = Instant::now();
+        let mut debounce = Debounce::new(vec![stamp(1)], DEBOUNCE);
+        assert_eq!(debounce.check(vec![stamp(2)], start), None);
+        assert_eq!(debounce.check(vec![stamp(3)], start + DEBOUNCE), None);
+        assert_eq!(debounce.check(vec![stamp(3)], start + DEBOUNCE * 3 / 2), None);
+        assert_eq!(debounce.check(vec![stamp(3)], start + DEBOUNCE * 2), Some(vec![0]));
+    }
+
+    #[test]
+    fn missing_files_are_waited_for() {
+        let start = Instant::now();
+        let mut debounce = Debounce::new(vec![stamp(1), stamp(2)], DEBOUNCE);
+        assert_eq!(debounce.check(vec![None, stamp(2)], start), None);
+        assert_eq!(debounce.check(vec![None, stamp(2)], start + DEBOUNCE * 2), None);
+        assert_eq!(debounce.check(vec![stamp(4), stamp(2)], start + DEBOUNCE * 2), None);
+        assert_eq!(debounce.check(vec![stamp(4), stamp(2)], start + DEBOUNCE * 3), Some(vec![0]));
+    }
+
+    #[test]
+    fn files_changed_back_are_not_reloaded() {
+        let start = Instant::now();
+        let mut debounce = Debounce::new(vec![stamp(1)], DEBOUNCE);
+        assert_eq!(debounce.check(vec![stamp(2)], start), None);
+        assert_eq!(debounce.check(vec![stamp(1)], start + DEBOUNCE), None);
+        // the debounce starts over when the files change again
+        assert_eq!(debounce.check(vec![stamp(2)], start + DEBOUNCE * 2), None);
+        assert_eq!(debounce.check(vec![stamp(2)], start + DEBOUNCE * 3), Some(vec![0]));
+    }
+
+    #[test]
+    fn watched_paths_list_the_root_and_patches() {
+        let builder = WurflBuilder::new().root("/data/wurfl.zip").patches(&["/data/patch.xml"]);
+        assert_eq!(watched_paths(&builder), Some(vec!["/data/wurfl.zip".to_string(), "/data/patch.xml".to_string()]));
+        assert_eq!(watched_paths(&WurflBuilder::new()), None);
+    }
+}
@@ -6,7 +6,50 @@ use thiserror::Error;
 // Custom error for WURFL handle operations
 #[derive(Error, Debug)]
 pub struct WurflError {
+    /// the kind of error, use it to decide how to react to the error
+    pub kind: WurflErrorKind,
     pub msg: String,
+    /// settings rejected while validating a `WurflBuilder` or an `UpdaterConfig`, empty for any other error
+    pub invalid_settings: Vec<InvalidSetting>,
+}
+
+impl WurflError {
+    /// Returns the kind of this error
+    pub fn kind(&self) -> WurflErrorKind {
+        self.kind
+    }
+
+    fn new(kind: WurflErrorKind, msg: String) -> WurflError {
+        WurflError { kind, msg, invalid_settings: Vec::new() }
+    }
+
+    /// Creates an error from the last error code and message held by the WURFL engine
+    fn from_handle(wh: wurfl_handle) -> WurflError {
+        let code = unsafe { wurfl_get_error_code(wh) };
+        WurflError::new(to_error_kind(code), Wurfl::get_error_message(wh))
+    }
+
+    /// Creates an error that lists all the settings rejected by the engine builder. When all of them were rejected
+    /// for the same kind of error (ie: a missing root file), the error has that kind, otherwise it is `InvalidSettings`
+    fn from_invalid_settings(invalid_settings: Vec<InvalidSetting>) -> WurflError {
+        WurflError::from_rejected_settings("engine", invalid_settings)
+    }
+
+    /// Creates an error that lists all the settings rejected by `Wurfl::configure_updater`, with the same kind as
+    /// `from_invalid_settings`
+    fn from_invalid_updater_settings(invalid_settings: Vec<InvalidSetting>) -> WurflError {
+        WurflError::from_rejected_settings("updater", invalid_settings)
+    }
+
+    fn from_rejected_settings(target: &str, invalid_settings: Vec<InvalidSetting>) -> WurflError {
+        let details: Vec<String> = invalid_settings.iter().map(|s| s.to_string()).collect();
+        let msg = format!("Invalid WURFL {} settings: {}", target, details.join("; "));
+        let kind = match invalid_settings.split_first() {
+            Some((first, others)) if others.iter().all(|s| s.kind == first.kind) => first.kind,
+            _ => WurflErrorKind::InvalidSettings,
+        };
+        WurflError { kind, msg, invalid_settings }
+    }
 }
 
 impl std::fmt::Display for WurflError {
@@ -15,30 +58,163 @@ impl std::fmt::Display for WurflError {
     }
 }
 
+/// Enumeration of the kinds of error returned by the WURFL API.
+/// Most of them map a `wurfl_error` code of the InFuze C library, the others are raised by the Rust wrapper itself.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum WurflErrorKind {
+    InvalidHandle,
+    AlreadyLoaded,
+    FileNotFound,
+    UnexpectedEndOfFile,
+    InputOutputFailure,
+    DeviceNotFound,
+    CapabilityNotFound,
+    InvalidCapabilityValue,
+    VirtualCapabilityNotFound,
+    CantLoadCapabilityNotFound,
+    CantLoadVirtualCapabilityNotFound,
+    EmptyId,
+    CapabilityGroupNotFound,
+    CapabilityGroupMismatch,
+    DeviceAlreadyDefined,
+    UserAgentAlreadyDefined,
+    DeviceHierarchyCircularReference,
+    Unknown,
+    InvalidUserAgentPriority,
+    InvalidParameter,
+    InvalidCacheSize,
+    XmlConsistency,
+    Internal,
+    VirtualCapabilityNotAvailable,
+    MissingUserAgent,
+    XmlParse,
+    UpdaterInvalidDataUrl,
+    UpdaterInvalidLicense,
+    UpdaterNetworkError,
+    EngineNotInitialized,
+    UpdaterAlreadyRunning,
+    UpdaterNotRunning,
+    UpdaterTooManyRequests,
+    UpdaterCmdlineDownloaderUnavailable,
+    UpdaterTimedOut,
+    RootNotSet,
+    WrongEngineTarget,
+    CannotFilterStaticCap,
+    UnableToAllocateMemory,
+    EngineNotLoaded,
+    UpdaterCannotStartThread,
+    EnumEmptySet,
+    UpdaterWrongDataFormat,
+    /// Several `WurflBuilder` settings are invalid for different kinds of error, see `WurflError::invalid_settings`
+    InvalidSettings,
+    /// A `HeaderSource` panicked while the engine retrieved a header during a lookup
+    HeaderSourcePanicked,
+    /// A string could not be passed to or read from the C library (ie: it contains a nul character or invalid UTF-8)
+    InvalidString,
+    /// An error code this version of the wrapper does not know about
+    Other(u32),
+}
+
+/// A WURFL engine or updater setting rejected while validating a `WurflBuilder` or an `UpdaterConfig`
+#[derive(Clone, PartialEq, Debug)]
+pub struct InvalidSetting {
+    /// name of the rejected setting (ie: "root", "patch", "cache_provider")
+    pub setting: &'static str,
+    /// the kind of error, as reported by the WURFL engine for the settings it rejected
+    pub kind: WurflErrorKind,
+    /// the reason why the setting has been rejected
+    pub reason: String,
+}
+
+impl std::fmt::Display for InvalidSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.setting, self.reason)
+    }
+}
+
 /// This represents a condition where no error has occurred
 const WURFL_OK: u32 = 0;
 
+/// Largest size accepted for a cache provider by the WURFL engine
+const MAX_CACHE_SIZE: usize = i32::MAX as usize;
+
 /// Enumeration of the cache providers supported by the WURFL Engine
+#[derive(Clone, PartialEq, Debug)]
 pub enum WurflCacheProvider {
     /// No cache is used
     NoCache,
-    /// An LRU cache provider is used
+    /// An LRU cache provider is used, holding up to `size` detected devices
+    Lru { size: NonZeroUsize },
+    /// A double LRU cache provider is used: one cache holds up to `useragent_size` User-Agents, the other one up to
+    /// `device_size` devices
+    DoubleLru { useragent_size: NonZeroUsize, device_size: NonZeroUsize },
+    /// An LRU cache provider is used, whose size is passed as `cache_extra_config` to `Wurfl::new`
+    #[deprecated(note = "use WurflCacheProvider::Lru, which carries the cache size")]
     LRU,
 }
 
+/// Enumeration of the WURFL engine targets, used to trade detection accuracy for lookup speed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EngineTarget {
+    /// Favours detection accuracy over lookup speed
+    HighAccuracy,
+    /// Favours lookup speed over detection accuracy
+    HighPerformance,
+    /// The engine target used when none is set
+    Default,
+    /// Detects desktop browsers with a faster, less accurate matcher
+    FastDesktopBrowserMatch,
+}
+
+/// Enumeration of the User-Agent priorities, used to choose which User-Agent is used for detection when a request
+/// comes from a sideloaded browser
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UserAgentPriority {
+    /// The sideloaded browser User-Agent overrides the plain User-Agent header
+    OverrideSideloadedBrowserUserAgent,
+    /// The plain User-Agent header is always used
+    UsePlainUserAgent,
+}
+
+/// Enumeration of the boolean attributes that can be set on the WURFL engine
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WurflAttribute {
+    /// Enables the experimental detection based on extra headers
+    ExtraHeadersExperimental,
+}
+
 /// Enumeration of the device matching types of the WURFL API
-#[derive(PartialEq, Debug)]
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum MatchType {
     WurflMatchTypeExact,
     WurflMatchTypeConclusive,
     WurflMatchTypeRecovery,
     WurflMatchTypeCatchall,
+    /// deprecated by the C API, which should not return it anymore
+    WurflMatchTypeHighPerformance,
     WurflMatchTypeNone,
     WurflMatchTypeCached,
+    /// returned when the engine target is `EngineTarget::FastDesktopBrowserMatch`
+    WurflMatchTypeFastDesktopBrowserMatch,
+    /// a match type this version of the wrapper does not know about
+    Unknown(u32),
+}
+
+/// Formats of the WURFL data passed to `Wurfl::reload_from_bytes`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DataFormat {
+    /// Uncompressed wurfl.xml
+    Plain,
+    /// wurfl.zip
+    Zip,
+    /// wurfl.xml.gz
+    Gz,
 }
 
 /// Enumeration of the available update frequencies for the WURFL updater process
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum WurflUpdaterFrequency {
     /// Daily updates
     WurflUpdaterFrequencyDaily,
@@ -53,24 +229,169 @@ pub enum WurflEnumType {
     WurflEnumWurflID,
 }
 
-/// convenience method for get a rust str from a C char array pointer
-fn to_str<'a>(char_seq: *const c_char) -> &'a str {
-    let c_str = unsafe { CStr::from_ptr(char_seq) };
-    return c_str.to_str().unwrap();
+/// convenience method for get a rust CStr from a C char array pointer, a NULL pointer is returned as an empty string
+fn to_c_str<'a>(char_seq: *const c_char) -> &'a CStr {
+    if char_seq.is_null() {
+        return Default::default();
+    }
+    unsafe { CStr::from_ptr(char_seq) }
+}
+
+/// convenience method for get a rust str from a C char array pointer.
+/// Invalid UTF-8 sequences (ie: coming from a hostile User-Agent) are replaced with U+FFFD instead of panicking
+fn to_str<'a>(char_seq: *const c_char) -> Cow<'a, str> {
+    to_c_str(char_seq).to_string_lossy()
 }
 
 /// convenience method for get a rust String from a C char array pointer
 fn to_owned_string(char_seq: *const c_char) -> String {
-    let c_str = unsafe { CStr::from_ptr(char_seq) };
-    let str_value = c_str.to_str().unwrap();
-    let ret_val = String::from(str_value);
-    return ret_val;
+    to_str(char_seq).into_owned()
+}
+
+// The locks of the wrapper guard values that are always left consistent (ie: an Arc replaced whole), so a lock
+// poisoned by a panicking thread still holds a valid value: the helpers below use it as is.
+
+fn read_lock<T>(lock: &RwLock<T>) -> std::sync::RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|poisoned| poisoned.into_inner())
 }
 
-fn to_cache_provider(cp: WurflCacheProvider) -> wurfl_cache_provider {
+fn write_lock<T>(lock: &RwLock<T>) -> std::sync::RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Runs Rust code called back by libwurfl (ie: user callbacks), returning None if it panicked. The panic is stopped
+/// here because unwinding across the FFI boundary is undefined behaviour.
+fn catch_ffi_panic<R, F: FnOnce() -> R>(f: F) -> Option<R> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).ok()
+}
+
+/// returns the C cache provider and its configuration string, or None when no cache provider must be set
+#[allow(deprecated)]
+fn to_cache_provider(cp: &WurflCacheProvider) -> Option<(wurfl_cache_provider, String)> {
     match cp {
-        WurflCacheProvider::NoCache => wurfl_cache_provider_WURFL_CACHE_PROVIDER_NONE,
-        WurflCacheProvider::LRU => wurfl_cache_provider_WURFL_CACHE_PROVIDER_LRU
+        WurflCacheProvider::NoCache => None,
+        WurflCacheProvider::Lru { size } => Some((wurfl_cache_provider_WURFL_CACHE_PROVIDER_LRU, size.to_string())),
+        WurflCacheProvider::DoubleLru { useragent_size, device_size } => {
+            Some((wurfl_cache_provider_WURFL_CACHE_PROVIDER_DOUBLE_LRU, format!("{},{}", useragent_size, device_size)))
+        }
+        // the legacy LRU variant is converted to Lru before being applied
+        WurflCacheProvider::LRU => None
+    }
+}
+
+fn to_error_kind(we: wurfl_error) -> WurflErrorKind {
+    match we {
+        wurfl_error_WURFL_ERROR_INVALID_HANDLE => WurflErrorKind::InvalidHandle,
+        wurfl_error_WURFL_ERROR_ALREADY_LOAD => WurflErrorKind::AlreadyLoaded,
+        wurfl_error_WURFL_ERROR_FILE_NOT_FOUND => WurflErrorKind::FileNotFound,
+        wurfl_error_WURFL_ERROR_UNEXPECTED_END_OF_FILE => WurflErrorKind::UnexpectedEndOfFile,
+        wurfl_error_WURFL_ERROR_INPUT_OUTPUT_FAILURE => WurflErrorKind::InputOutputFailure,
+        wurfl_error_WURFL_ERROR_DEVICE_NOT_FOUND => WurflErrorKind::DeviceNotFound,
+        wurfl_error_WURFL_ERROR_CAPABILITY_NOT_FOUND => WurflErrorKind::CapabilityNotFound,
+        wurfl_error_WURFL_ERROR_INVALID_CAPABILITY_VALUE => WurflErrorKind::InvalidCapabilityValue,
+        wurfl_error_WURFL_ERROR_VIRTUAL_CAPABILITY_NOT_FOUND => WurflErrorKind::VirtualCapabilityNotFound,
+        wurfl_error_WURFL_ERROR_CANT_LOAD_CAPABILITY_NOT_FOUND => WurflErrorKind::CantLoadCapabilityNotFound,
+        wurfl_error_WURFL_ERROR_CANT_LOAD_VIRTUAL_CAPABILITY_NOT_FOUND => WurflErrorKind::CantLoadVirtualCapabilityNotFound,
+        wurfl_error_WURFL_ERROR_EMPTY_ID => WurflErrorKind::EmptyId,
+        wurfl_error_WURFL_ERROR_CAPABILITY_GROUP_NOT_FOUND => WurflErrorKind::CapabilityGroupNotFound,
+        wurfl_error_WURFL_ERROR_CAPABILITY_GROUP_MISMATCH => WurflErrorKind::CapabilityGroupMismatch,
+        wurfl_error_WURFL_ERROR_DEVICE_ALREADY_DEFINED => WurflErrorKind::DeviceAlreadyDefined,
+        wurfl_error_WURFL_ERROR_USERAGENT_ALREADY_DEFINED => WurflErrorKind::UserAgentAlreadyDefined,
+        wurfl_error_WURFL_ERROR_DEVICE_HIERARCHY_CIRCULAR_REFERENCE => WurflErrorKind::DeviceHierarchyCircularReference,
+        wurfl_error_WURFL_ERROR_UNKNOWN => WurflErrorKind::Unknown,
+        wurfl_error_WURFL_ERROR_INVALID_USERAGENT_PRIORITY => WurflErrorKind::InvalidUserAgentPriority,
+        wurfl_error_WURFL_ERROR_INVALID_PARAMETER => WurflErrorKind::InvalidParameter,
+        wurfl_error_WURFL_ERROR_INVALID_CACHE_SIZE => WurflErrorKind::InvalidCacheSize,
+        wurfl_error_WURFL_ERROR_XML_CONSISTENCY => WurflErrorKind::XmlConsistency,
+        wurfl_error_WURFL_ERROR_INTERNAL => WurflErrorKind::Internal,
+        wurfl_error_WURFL_ERROR_VIRTUAL_CAPABILITY_NOT_AVAILABLE => WurflErrorKind::VirtualCapabilityNotAvailable,
+        wurfl_error_WURFL_ERROR_MISSING_USERAGENT => WurflErrorKind::MissingUserAgent,
+        wurfl_error_WURFL_ERROR_XML_PARSE => WurflErrorKind::XmlParse,
+        wurfl_error_WURFL_ERROR_UPDATER_INVALID_DATA_URL => WurflErrorKind::UpdaterInvalidDataUrl,
+        wurfl_error_WURFL_ERROR_UPDATER_INVALID_LICENSE => WurflErrorKind::UpdaterInvalidLicense,
+        wurfl_error_WURFL_ERROR_UPDATER_NETWORK_ERROR => WurflErrorKind::UpdaterNetworkError,
+        wurfl_error_WURFL_ERROR_ENGINE_NOT_INITIALIZED => WurflErrorKind::EngineNotInitialized,
+        wurfl_error_WURFL_ERROR_UPDATER_ALREADY_RUNNING => WurflErrorKind::UpdaterAlreadyRunning,
+        wurfl_error_WURFL_ERROR_UPDATER_NOT_RUNNING => WurflErrorKind::UpdaterNotRunning,
+        wurfl_error_WURFL_ERROR_UPDATER_TOO_MANY_REQUESTS => WurflErrorKind::UpdaterTooManyRequests,
+        wurfl_error_WURFL_ERROR_UPDATER_CMDLINE_DOWNLOADER_UNAVAILABLE => WurflErrorKind::UpdaterCmdlineDownloaderUnavailable,
+        wurfl_error_WURFL_ERROR_UPDATER_TIMEDOUT => WurflErrorKind::UpdaterTimedOut,
+        wurfl_error_WURFL_ERROR_ROOT_NOT_SET => WurflErrorKind::RootNotSet,
+        wurfl_error_WURFL_ERROR_WRONG_ENGINE_TARGET => WurflErrorKind::WrongEngineTarget,
+        wurfl_error_WURFL_ERROR_CANNOT_FILTER_STATIC_CAP => WurflErrorKind::CannotFilterStaticCap,
+        wurfl_error_WURFL_ENGINE_UNABLE_TO_ALLOCATE_MEMORY => WurflErrorKind::UnableToAllocateMemory,
+        wurfl_error_WURFL_ENGINE_NOT_LOADED => WurflErrorKind::EngineNotLoaded,
+        wurfl_error_WURFL_ERROR_UPDATER_CANNOT_START_THREAD => WurflErrorKind::UpdaterCannotStartThread,
+        wurfl_error_WURFL_ERROR_ENUM_EMPTY_SET => WurflErrorKind::EnumEmptySet,
+        wurfl_error_WURFL_ERROR_UPDATER_WRONG_DATA_FORMAT => WurflErrorKind::UpdaterWrongDataFormat,
+        // an error has been reported without setting its code
+        wurfl_error_WURFL_OK => WurflErrorKind::Unknown,
+        _ => WurflErrorKind::Other(we)
+    }
+}
+
+fn to_match_type(mt: wurfl_match_type) -> MatchType {
+    match mt {
+        wurfl_match_type_WURFL_MATCH_TYPE_EXACT => MatchType::WurflMatchTypeExact,
+        wurfl_match_type_WURFL_MATCH_TYPE_CONCLUSIVE => MatchType::WurflMatchTypeConclusive,
+        wurfl_match_type_WURFL_MATCH_TYPE_RECOVERY => MatchType::WurflMatchTypeRecovery,
+        wurfl_match_type_WURFL_MATCH_TYPE_CATCHALL => MatchType::WurflMatchTypeCatchall,
+        wurfl_match_type_WURFL_MATCH_TYPE_HIGHPERFORMANCE => MatchType::WurflMatchTypeHighPerformance,
+        wurfl_match_type_WURFL_MATCH_TYPE_NONE => MatchType::WurflMatchTypeNone,
+        wurfl_match_type_WURFL_MATCH_TYPE_CACHED => MatchType::WurflMatchTypeCached,
+        wurfl_match_type_WURFL_MATCH_TYPE_FAST_DESKTOP_BROWSER_MATCH => MatchType::WurflMatchTypeFastDesktopBrowserMatch,
+        _ => MatchType::Unknown(mt)
+    }
+}
+
+fn to_engine_target(et: EngineTarget) -> wurfl_engine_target {
+    match et {
+        EngineTarget::HighAccuracy => wurfl_engine_target_WURFL_ENGINE_TARGET_HIGH_ACCURACY,
+        EngineTarget::HighPerformance => wurfl_engine_target_WURFL_ENGINE_TARGET_HIGH_PERFORMANCE,
+        EngineTarget::Default => wurfl_engine_target_WURFL_ENGINE_TARGET_DEFAULT,
+        EngineTarget::FastDesktopBrowserMatch => wurfl_engine_target_WURFL_ENGINE_TARGET_FAST_DESKTOP_BROWSER_MATCH
+    }
+}
+
+fn from_engine_target(et: wurfl_engine_target) -> EngineTarget {
+    match et {
+        wurfl_engine_target_WURFL_ENGINE_TARGET_HIGH_ACCURACY => EngineTarget::HighAccuracy,
+        wurfl_engine_target_WURFL_ENGINE_TARGET_HIGH_PERFORMANCE => EngineTarget::HighPerformance,
+        wurfl_engine_target_WURFL_ENGINE_TARGET_FAST_DESKTOP_BROWSER_MATCH => EngineTarget::FastDesktopBrowserMatch,
+        _ => EngineTarget::Default
+    }
+}
+
+fn to_useragent_priority(up: UserAgentPriority) -> wurfl_useragent_priority {
+    match up {
+        UserAgentPriority::OverrideSideloadedBrowserUserAgent => wurfl_useragent_priority_WURFL_USERAGENT_PRIORITY_OVERRIDE_SIDELOADED_BROWSER_USERAGENT,
+        UserAgentPriority::UsePlainUserAgent => wurfl_useragent_priority_WURFL_USERAGENT_PRIORITY_USE_PLAIN_USERAGENT
+    }
+}
+
+fn from_useragent_priority(up: wurfl_useragent_priority) -> Option<UserAgentPriority> {
+    match up {
+        wurfl_useragent_priority_WURFL_USERAGENT_PRIORITY_OVERRIDE_SIDELOADED_BROWSER_USERAGENT => Some(UserAgentPriority::OverrideSideloadedBrowserUserAgent),
+        wurfl_useragent_priority_WURFL_USERAGENT_PRIORITY_USE_PLAIN_USERAGENT => Some(UserAgentPriority::UsePlainUserAgent),
+        _ => None
+    }
+}
+
+fn to_wurfl_attr(attr: WurflAttribute) -> wurfl_attr {
+    match attr {
+        WurflAttribute::ExtraHeadersExperimental => wurfl_attr_WURFL_ATTR_EXTRA_HEADERS_EXPERIMENTAL
+    }
+}
+
+fn to_data_format(df: DataFormat) -> wurfl_updater_data_format {
+    match df {
+        DataFormat::Plain => wurfl_updater_data_format_WURFL_UPDATER_DATA_FORMAT_PLAIN,
+        DataFormat::Zip => wurfl_updater_data_format_WURFL_UPDATER_DATA_FORMAT_ZIP,
+        DataFormat::Gz => wurfl_updater_data_format_WURFL_UPDATER_DATA_FORMAT_GZ,
     }
 }
 
@@ -82,121 +403,68 @@ fn to_wurfl_enum_type(et: WurflEnumType) -> wurfl_enum_type {
     }
 }
 
-/// WURFL holds data and exposes methods used to perform device detection and access device capabilities.
-pub struct Wurfl {
+/// Owns the libwurfl engine handle, which is destroyed when the last Wurfl or detached Device sharing it is dropped
+struct EngineHandle {
     wurfl: wurfl_handle,
-    _important_header_names: Vec<String>,
-    // used for header ignore-case comparison and to avoid converting well-know header names every time
-    // key: a lowercase header name, value: its CString conversion
-    important_header_cstring_names: HashMap<String, CString>,
+    // receives the updater log lines, libwurfl keeps a pointer to the engine handle until the handle is destroyed
+    updater_log_sink: UpdaterLogSink,
+    updater: UpdaterControl,
+    // bumped by every in-place reload: the C important header objects hold data of the generation they were created in
+    data_generation: std::sync::atomic::AtomicU64,
+    // the memory file holding the data of an engine built with WurflBuilder::root_from_bytes
+    root_file: Option<std::fs::File>,
+    // C important header objects on which no header has been set, with their data generation
+    important_header_pool: Mutex<Vec<(u64, wurfl_important_header_handle)>>,
 }
 
-/// Implementation of the WURFL API engine. Loads the WURFL file, exposes methods to perform device detection and query
-/// the detected device capabilities.
-impl Wurfl {
-    /// Creates the wurfl engine.
-    /// Parameters :
-    /// wurfl_xml : path to the wurfl.xml/zip file
-    /// patches : vector of paths of patches files to load
-    /// cap_filter : list of capabilities used; allow to init engine without loading all 500+ caps
-    /// cache_provider : WurflCacheProviderLru or NoCache
-    /// cache_extra_config : size of lru cache in the form "100000"
-    pub fn new(wurfl_xml: &str, patches: Option<&[&str]>, cap_filter: Option<&[&str]>,
-               cache_provider: WurflCacheProvider, cache_extra_config: Option<&str>) -> Result<Wurfl, WurflError> {
-        let wh = unsafe { wurfl_create() };
-        if wh.is_null() {
-            return Err(WurflError { msg: "Wurfl handle is NULL".to_string() });
-        }
-
-        let c_str_path = match CString::new(wurfl_xml) {
-            Ok(c) => c,
-            Err(_) => return Err(WurflError { msg: "Error creating CString from wurfl xml file path".to_string() })
-        };
-        let c_chars_path = c_str_path.as_ptr() as *const c_char;
-        // Set the root path that will be used to load the engine
-        let we = unsafe { wurfl_set_root(wh, c_chars_path) };
-        if we != WURFL_OK {
-            let error_message = Wurfl::get_error_message(wh);
-            let werr = WurflError { msg: error_message };
-            return Err(werr);
-        }
-
-
-        // let's set the cache provider - if specified
-        match cache_provider {
-            WurflCacheProvider::LRU => {
-                let cache_provider_val = to_cache_provider(cache_provider);
-
-                if cache_extra_config.is_some() {
-                    let u_cache_extra_config = cache_extra_config.unwrap();
-                    let c_str_extra_cache_config = match CString::new(u_cache_extra_config) {
-                        Ok(ecc) => ecc,
-                        Err(_) => return Err(WurflError { msg: "Invalid cache_extra_config passed in create WURFL engine. Possibly a nul character in the input string".to_string() })
-                    };
-                    unsafe {
-                        let we = wurfl_set_cache_provider(wh, cache_provider_val, c_str_extra_cache_config.as_ptr());
-                        if we != WURFL_OK {
-                            let err_msg = Wurfl::get_error_message(wh);
-                            return Err(WurflError { msg: err_msg });
-                        }
-                    };
-                };
-            }
-            _ => {}
+impl Drop for EngineHandle {
+    fn drop(&mut self) {
+        //println!("Destroying... WURFL engine");
+        // an updater started without keeping its UpdaterHandle must not outlive the engine
+        if lock(&self.updater.run).current.is_some() {
+            unsafe { wurfl_updater_stop(self.wurfl) };
         }
-
-        // setting patches
-        if patches.is_some() {
-            let u_patches = patches.unwrap();
-            for p in u_patches {
-                let s_slice: &str = &p[..];
-                let c_str_p = match CString::new(s_slice) {
-                    Ok(cs) => cs,
-                    Err(e) => {
-                        let err_msg = format!("Invalid patch path passed: {}", e.to_string());
-                        return Err(WurflError { msg: err_msg });
-                    }
-                };
-                let we = unsafe { wurfl_add_patch(wh, c_str_p.as_ptr()) };
-                if we != WURFL_OK {
-                    let error_message = Wurfl::get_error_message(wh);
-                    let werr = WurflError { msg: error_message };
-                    return Err(werr);
-                }
-                //println!("Adding patch file : {}", p);
+        if let Ok(pool) = self.important_header_pool.get_mut() {
+            for (_, cih) in pool.drain(..) {
+                unsafe { wurfl_important_header_destroy(cih) };
             }
         }
+        unsafe { wurfl_destroy(self.wurfl) };
+        // closed only once libwurfl is done with the root path, so that no other file can reuse its fd number meanwhile
+        drop(self.root_file.take());
+    }
+}
 
+// makes the engine handle transferable between threads
+unsafe impl Send for EngineHandle {}
 
-        // filter capabilities in engine
-        if cap_filter.is_some() {
-            let u_filter = cap_filter.unwrap();
-            for cap_name in u_filter {
-                let s_slice: &str = &cap_name[..];
+// assumes engine handle thread safety, which, in our case is supported by the underlying Infuze C library
+unsafe impl Sync for EngineHandle {}
 
-                let c_str_cap_name = match CString::new(s_slice) {
-                    Ok(cs) => cs,
-                    Err(e) => {
-                        let err_msg = format!("Invalid capability filter passed: {}", e.to_string());
-                        return Err(WurflError { msg: err_msg });
-                    }
-                };
-                let we = unsafe { wurfl_add_requested_capability(wh, c_str_cap_name.as_ptr()) };
-                if we != WURFL_OK {
-                    let error_message = Wurfl::get_error_message(wh);
-                    return Err(WurflError { msg: error_message });
-                }
-                //println!("Adding capability : {}", cap_name);
-            }
-        }
+/// WURFL holds data and exposes methods used to perform device detection and access device capabilities.
+pub struct Wurfl {
+    // copy of engine.wurfl, valid as long as this struct holds the engine
+    wurfl: wurfl_handle,
+    engine: Arc<EngineHandle>,
+    // identifies this engine in the log events
+    engine_id: u64,
+    // replaced when the engine is reloaded in place
+    important_header_table: RwLock<Arc<ImportantHeaderTable>>,
+    // the settings this engine was built with, used to build its replacements; its root follows in place reloads
+    builder: RwLock<WurflBuilder>,
+}
 
-        let wel = unsafe { wurfl_load(wh) };
-        if wel != WURFL_OK {
-            let load_error_message = Wurfl::get_error_message(wh);
-            let w_load_err = WurflError { msg: load_error_message };
-            return Err(w_load_err);
-        }
+/// The important headers of the loaded WURFL data
+struct ImportantHeaderTable {
+    names: ImportantHeaderNames,
+    // used for header ignore-case comparison and to avoid converting well-know header names every time
+    // key: a lowercase header name, value: its CString conversion
+    cstring_names: HashMap<String, CString>,
+}
 
+impl ImportantHeaderTable {
+    /// Reads the important headers of the data loaded by the engine
+    fn read(wh: wurfl_handle) -> Result<ImportantHeaderTable, WurflError> {
         let mut imp_h_names = vec![];
         let mut imh_h_cstr_names = HashMap::new();
 
@@ -204,8 +472,7 @@ impl Wurfl {
         unsafe {
             let ihe = wurfl_get_important_header_enumerator(wh);
             if ihe.is_null() {
-                let err_msg = Wurfl::get_error_message(wh);
-                return Err(WurflError { msg: err_msg });
+                return Err(WurflError::from_handle(wh));
             }
 
             while wurfl_important_header_enumerator_is_valid(ihe) != 0 {
@@ -218,7 +485,8 @@ impl Wurfl {
                 let c_str_header_name = match CString::new(str_headerName.clone()) {
                     Ok(chn) => chn,
                     Err(_) => {
-                        return Err(WurflError { msg: format!("Cannot convert important header name {} to C String", str_headerName) });
+                        wurfl_important_header_enumerator_destroy(ihe);
+                        return Err(WurflError::new(WurflErrorKind::InvalidString, format!("Cannot convert important header name {} to C String", str_headerName)));
                     }
                 };
                 // append to slice and map
@@ -230,39 +498,128 @@ impl Wurfl {
             wurfl_important_header_enumerator_destroy(ihe);
         }
 
+        Ok(ImportantHeaderTable {
+            names: ImportantHeaderNames { names: imp_h_names },
+            cstring_names: imh_h_cstr_names,
+        })
+    }
+}
+
+/// Implementation of the WURFL API engine. Loads the WURFL file, exposes methods to perform device detection and query
+/// the detected device capabilities.
+impl Wurfl {
+    /// Creates the wurfl engine.
+    /// Parameters :
+    /// wurfl_xml : path to the wurfl.xml/zip file
+    /// patches : vector of paths of patches files to load
+    /// cap_filter : list of capabilities used; allow to init engine without loading all 500+ caps
+    /// cache_provider : NoCache, Lru or DoubleLru
+    /// cache_extra_config : size of the cache in the form "100000", used only by the deprecated LRU cache provider
+    ///
+    /// Use `WurflBuilder` to set any other engine option.
+    pub fn new(wurfl_xml: &str, patches: Option<&[&str]>, cap_filter: Option<&[&str]>,
+               cache_provider: WurflCacheProvider, cache_extra_config: Option<&str>) -> Result<Wurfl, WurflError> {
+        let mut builder = WurflBuilder::new()
+            .root(wurfl_xml)
+            .cache_provider(cache_provider)
+            .legacy_cache_extra_config(cache_extra_config);
+        if let Some(p) = patches {
+            builder = builder.patches(p);
+        }
+        if let Some(cf) = cap_filter {
+            builder = builder.requested_capabilities(cf);
+        }
+        builder.build()
+    }
+
+    /// Builds the Wurfl struct around a handle on which `wurfl_load` has already succeeded
+    fn from_loaded_handle(wh: wurfl_handle, engine_id: u64, builder: &WurflBuilder, root_file: Option<std::fs::File>) -> Result<Wurfl, WurflError> {
+        let important_header_table = ImportantHeaderTable::read(wh)?;
+
         let wurfl_engine = Wurfl {
             wurfl: wh,
-            _important_header_names: imp_h_names,
-            important_header_cstring_names: imh_h_cstr_names,
+            engine: Arc::new(EngineHandle {
+                wurfl: wh,
+                updater_log_sink: UpdaterLogSink::with_log_path(builder.updater_log_path.clone()),
+                updater: UpdaterControl::default(),
+                root_file,
+                data_generation: std::sync::atomic::AtomicU64::new(0),
+                important_header_pool: Mutex::new(Vec::new()),
+            }),
+            engine_id,
+            important_header_table: RwLock::new(Arc::new(important_header_table)),
+            builder: RwLock::new(builder.without_root_data()),
         };
 
         return Ok(wurfl_engine);
     }
 
+    /// Returns the settings this engine was built with. After `reload_from_path` the root is the reloaded file; after
+    /// `reload_from_bytes` the root is unset, as the loaded data is not on disk anymore, and building from the
+    /// returned settings fails until a root is set again. The same goes for an engine built with
+    /// `WurflBuilder::root_from_bytes`, whose data is not kept by the settings.
+    pub fn get_builder(&self) -> WurflBuilder {
+        read_lock(&self.builder).clone()
+    }
+
+    /// Returns the identifier of this engine, unique within the process and reported in the engine log events
+    pub fn get_engine_id(&self) -> u64 {
+        self.engine_id
+    }
+
     /// Returns the last error message held by the WURFL engine
     fn get_error_message(wh: wurfl_handle) -> String {
         let wu_err = unsafe { wurfl_get_error_message(wh) };
-        let err_msg = to_str(wu_err);
-        return err_msg.to_string();
+        return to_owned_string(wu_err);
     }
 
     /// Returns the current underlying WURFL API version
-    pub fn get_api_version(&self) -> &str {
+    pub fn get_api_version(&self) -> Cow<'_, str> {
         let c_buf: *const c_char = unsafe { wurfl_get_api_version() };
         let api_ver = to_str(c_buf);
         return api_ver;
     }
 
-    /// Returns the last load time of the WURFL file
-    pub fn get_last_load_time(&self) -> &str {
+    /// Returns the last load time of the WURFL file.
+    /// The value is copied, because the engine replaces it when it is reloaded
+    pub fn get_last_load_time(&self) -> String {
         let llt = unsafe { wurfl_get_last_load_time_as_string(self.wurfl) };
-        return to_str(llt);
+        return to_owned_string(llt);
     }
 
-    /// Returns information about the running WURFL engine and loaded file
-    pub fn get_info(&self) -> &str {
+    /// Returns information about the running WURFL engine and loaded file.
+    /// The value is copied, because the engine replaces it when it is reloaded
+    pub fn get_info(&self) -> String {
         let info = unsafe { wurfl_get_wurfl_info(self.wurfl) };
-        return to_str(info);
+        return to_owned_string(info);
+    }
+
+    /// Returns the engine target in use, set with `WurflBuilder::engine_target`
+    pub fn get_engine_target(&self) -> EngineTarget {
+        let et = unsafe { wurfl_get_engine_target(self.wurfl) };
+        from_engine_target(et)
+    }
+
+    /// Returns the name of the engine target in use, as reported by the WURFL engine
+    pub fn get_engine_target_as_string(&self) -> String {
+        let et = unsafe { wurfl_get_engine_target_as_string(self.wurfl) };
+        to_owned_string(et)
+    }
+
+    /// Returns the User-Agent priority in use, set with `WurflBuilder::useragent_priority`.
+    /// Returns an error if the engine reports an invalid priority
+    pub fn get_useragent_priority(&self) -> Result<UserAgentPriority, WurflError> {
+        let up = unsafe { wurfl_get_useragent_priority(self.wurfl) };
+        match from_useragent_priority(up) {
+            Some(priority) => Ok(priority),
+            None => Err(WurflError::new(WurflErrorKind::InvalidUserAgentPriority, format!("Invalid User-Agent priority {}", up))),
+        }
+    }
+
+    /// Returns the name of the User-Agent priority in use, as reported by the WURFL engine
+    pub fn get_useragent_priority_as_string(&self) -> Cow<'_, str> {
+        let up = unsafe { wurfl_get_useragent_priority_as_string(self.wurfl) };
+        to_str(up)
     }
 
     /// Returns all capabilities names
@@ -276,8 +633,7 @@ impl Wurfl {
             }
             while wurfl_enum_is_valid(caps_enum) == 1 {
                 let cap_name_ptr = wurfl_enum_get_name(caps_enum);
-                let cap_name = to_str(cap_name_ptr);
-                cap_names.push(cap_name.to_string());
+                cap_names.push(to_owned_string(cap_name_ptr));
                 wurfl_enum_move_next(caps_enum);
             }
             // once listed the capability names, destroy the enumeration created by the C lib
@@ -297,8 +653,7 @@ impl Wurfl {
             }
             while wurfl_enum_is_valid(vcaps_enum) == 1 {
                 let vcap_name_ptr = wurfl_enum_get_name(vcaps_enum);
-                let vcap_name = to_str(vcap_name_ptr);
-                vcap_names.push(vcap_name.to_string());
+                vcap_names.push(to_owned_string(vcap_name_ptr));
                 wurfl_enum_move_next(vcaps_enum);
             }
             // once listed the capability names, destroy the enumeration created by the C lib
@@ -308,21 +663,20 @@ impl Wurfl {
     }
 
     /// Retrieves device data based on the given User-Agent string
-    pub fn lookup_useragent(&self, user_agent: &str) -> Result<Device, WurflError> {
+    pub fn lookup_useragent(&self, user_agent: &str) -> Result<Device<'_>, WurflError> {
         let c_str_useragent = match CString::new(user_agent) {
             Ok(ua) => ua,
             Err(_) => {
                 let msg = format!("Unable to convert into a CString the User-Agent  {}", user_agent);
-                return Err(WurflError { msg });
+                return Err(WurflError::new(WurflErrorKind::InvalidString, msg));
             }  // failed to convert to C string
         };
         let d_handle = unsafe { wurfl_lookup_useragent(self.wurfl, c_str_useragent.as_ptr()) };
         if d_handle.is_null() {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Err(WurflError { msg: err_msg });
+            return Err(WurflError::from_handle(self.wurfl));
         }
         let device = Device {
-            wurfl: self.wurfl,
+            engine: Cow::Borrowed(&self.engine),
             device: d_handle,
         };
         return Result::Ok(device);
@@ -330,135 +684,48 @@ impl Wurfl {
 
     /// Retrieves device data based on HTTP request headers that can be passed in any data structures that implement the
     /// `IntoIterator` trait (for example: HashMap or Hyper framework HeaderMap.
-    pub fn lookup_with_headers<U, V, T: IntoIterator<Item=(U, V)>>(&self, headers: T) -> Result<Device, WurflError> where
+    pub fn lookup_with_headers<U, V, T: IntoIterator<Item=(U, V)>>(&self, headers: T) -> Result<Device<'_>, WurflError> where
         U: ToString,
         V: AsRef<[u8]> {
-        let cih = unsafe { wurfl_important_header_create(self.wurfl) };
-        if cih.is_null() {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Err(WurflError { msg: err_msg });
-        }
-
-        let ih_names_ref = &self.important_header_cstring_names;
-        for (key, value) in headers {
-            let low_key = &key.to_string().to_lowercase();
-            if ih_names_ref.contains_key(low_key)
-            {
-                let c_header_name = self.important_header_cstring_names.get(low_key).unwrap();
-
-                // get a &str from the generic header output
-                let utf_str_res = std::str::from_utf8(value.as_ref());
-                if utf_str_res.is_ok() {
-                    let utf_str = utf_str_res.unwrap();
-                    // create a C string from header value
-                    let c_header = match CString::new(utf_str) {
-                        Ok(c_h) => c_h,
-                        Err(_) => {
-                            // before returning error, free memory for the C important headers
-                            unsafe { wurfl_important_header_destroy(cih) };
-                            return Err(WurflError { msg: "Unable to convert header value to C string".to_string() });
-                        }
-                    };
-
-                    // add this header to cih
-                    unsafe {
-                        wurfl_important_header_set(cih, c_header_name.as_ptr(), c_header.as_ptr());
-                    }
-                }
-            }
-        }
-
-        let d_handle = unsafe { wurfl_lookup_with_important_header(self.wurfl, cih) };
-        if d_handle.is_null() {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Err(WurflError { msg: err_msg });
-        }
-        let device = Device {
-            wurfl: self.wurfl,
-            device: d_handle,
-        };
-        unsafe { wurfl_important_header_destroy(cih) };
-        return Ok(device);
+        let important_headers = self.collect_important_headers(headers);
+        self.lookup_with_header_source(&important_headers)
     }
 
     /// Retrieves device data based on a WURFL device ID and HTTP request headers that can be passed in any data structures that implement the
     /// `IntoIterator` trait (for example: HashMap or Hyper framework HeaderMap.
-    pub fn lookup_device_id_with_headers<U, V, T: IntoIterator<Item=(U, V)>>(&self, device_id: &str, headers: T) -> Result<Device, WurflError> where
+    pub fn lookup_device_id_with_headers<U, V, T: IntoIterator<Item=(U, V)>>(&self, device_id: &str, headers: T) -> Result<Device<'_>, WurflError> where
         U: ToString,
         V: AsRef<[u8]> {
+        let important_headers = self.collect_important_headers(headers);
+        self.lookup_device_id_with_header_source(device_id, &important_headers)
+    }
 
-        // Get device ID
-        let c_dev_id = match CString::new(device_id) {
-            Ok(did) => did,
-            Err(_) => {
-                let msg = format!("Unable to convert device id  {}! into a CString", device_id);
-                return Err(WurflError { msg });
-            }  // failed to convert to C string
-        };
-
-        let cih = unsafe { wurfl_important_header_create(self.wurfl) };
-        if cih.is_null() {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Err(WurflError { msg: err_msg });
-        }
-
-        let ih_names_ref = &self.important_header_cstring_names;
-        for (key, value) in headers {
-            let low_key = &key.to_string().to_lowercase();
-            if ih_names_ref.contains_key(low_key) {
-                let c_header_name = self.important_header_cstring_names.get(low_key).unwrap();
-
-                // get a &str from the generic header output
-                let utf_str_res = std::str::from_utf8(value.as_ref());
-                if utf_str_res.is_ok() {
-                    let utf_str = utf_str_res.unwrap();
-                    // create a C string from header value
-                    let c_header = match CString::new(utf_str) {
-                        Ok(c_h) => c_h,
-                        Err(_) => {
-                            // before returning error, free memory for the C important headers
-                            unsafe { wurfl_important_header_destroy(cih) };
-                            return Err(WurflError { msg: "Unable to convert header value to C string".to_string() });
-                        }
-                    };
-
-                    // add this header to cih
-                    unsafe {
-                        wurfl_important_header_set(cih, c_header_name.as_ptr(), c_header.as_ptr());
-                    }
-                }
-            }
-        }
-
-        let d_handle = unsafe { wurfl_get_device_with_important_header(self.wurfl, c_dev_id.as_ptr(), cih) };
-        if d_handle.is_null() {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Err(WurflError { msg: err_msg });
-        }
-        let device = Device {
-            wurfl: self.wurfl,
-            device: d_handle,
-        };
-        unsafe { wurfl_important_header_destroy(cih) };
-        return Ok(device);
+    /// Keeps the important headers only, so that the lookup does not search through the others. Values are not copied
+    fn collect_important_headers<U, V, T: IntoIterator<Item=(U, V)>>(&self, headers: T) -> Vec<(String, V)> where
+        U: ToString,
+        V: AsRef<[u8]> {
+        let table = self.important_header_table();
+        headers.into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .filter(|(key, _)| table.names.contains(key))
+            .collect()
     }
 
     /// Retrieves device data based on the WURFL device ID
-    pub fn lookup_device_id(&self, device_id: &str) -> Result<Device, WurflError> {
+    pub fn lookup_device_id(&self, device_id: &str) -> Result<Device<'_>, WurflError> {
         let c_dev_id = match CString::new(device_id) {
             Ok(did) => did,
             Err(_) => {
                 let msg = format!("Unable to convert device id  {}! into a CString", device_id);
-                return Err(WurflError { msg });
+                return Err(WurflError::new(WurflErrorKind::InvalidString, msg));
             }  // failed to convert to C string
         };
         let d_handle = unsafe { wurfl_get_device(self.wurfl, c_dev_id.as_ptr()) };
         if d_handle.is_null() {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Err(WurflError { msg: err_msg });
+            return Err(WurflError::from_handle(self.wurfl));
         }
         let device = Device {
-            wurfl: self.wurfl,
+            engine: Cow::Borrowed(&self.engine),
             device: d_handle,
         };
         return Result::Ok(device);
@@ -474,19 +741,10 @@ impl Wurfl {
         }
 
         while unsafe { wurfl_enum_is_valid(d_id_enum) != 0 } {
-            // convert device id to go string
-
-            let id_ptr = unsafe { wurfl_enum_get_name(d_id_enum) };
-            let c_str = unsafe { CStr::from_ptr(id_ptr) };
-            // the string coming from get_capability should be UTF-8 compliant
-            let id = match c_str.to_str() {
-                Ok(conv_id) => conv_id,
-                Err(_utf_err) => "" // empty, string, will be discarded later
-            };
-
-            if id.len() != 0 {
-                // add this id to the slice
-                dev_ids.push(id.to_string());
+            // decoded lossily, so that no device ID is dropped; a NULL name is read as empty and discarded
+            let id = to_owned_string(unsafe { wurfl_enum_get_name(d_id_enum) });
+            if !id.is_empty() {
+                dev_ids.push(id);
             }
             unsafe { wurfl_enum_move_next(d_id_enum) }
         }
@@ -525,105 +783,96 @@ impl Wurfl {
 
     // START UPDATER METHODS ------------------------------------------------------------------------
     /// Set the URL of the WURFL file to download in the update process
-    pub fn set_updater_data_url(&self, data_url: &str) -> Option<WurflError> {
+    pub fn updater_set_data_url(&self, data_url: &str) -> Result<(), WurflError> {
         let c_url = match CString::new(data_url) {
             Ok(cu) => cu,
-            Err(_) => return Some(WurflError { msg: "Unable to create C string for updater data URL".to_string() }),
+            Err(_) => return Err(WurflError::new(WurflErrorKind::InvalidString, "Unable to create C string for updater data URL".to_string())),
         };
 
         let url_set = unsafe { wurfl_updater_set_data_url(self.wurfl, c_url.as_ptr()) };
         if url_set != WURFL_OK {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Some(WurflError { msg: err_msg });
+            return Err(WurflError::from_handle(self.wurfl));
         }
-        return None;
+        lock(&self.engine.updater.settings).data_url = Some(data_url.to_string());
+        Ok(())
     }
 
     /// Sets the interval of update checks
-    pub fn set_updater_data_frequency(&self, freq: WurflUpdaterFrequency) -> Option<WurflError> {
+    pub fn updater_set_data_frequency(&self, freq: WurflUpdaterFrequency) -> Result<(), WurflError> {
         let c_freq = match freq {
             WurflUpdaterFrequency::WurflUpdaterFrequencyDaily => 0u32,
             WurflUpdaterFrequency::WurflUpdaterFrequencyWeekly => 1u32,
         };
 
-        unsafe {
-            let freq_set = wurfl_updater_set_data_frequency(self.wurfl, c_freq);
-            if freq_set != WURFL_OK {
-                let err_msg = Wurfl::get_error_message(self.wurfl);
-                return Some(WurflError { msg: err_msg });
-            }
+        if unsafe { wurfl_updater_set_data_frequency(self.wurfl, c_freq) } != WURFL_OK {
+            return Err(WurflError::from_handle(self.wurfl));
         }
-        return None;
+        lock(&self.engine.updater.settings).frequency = Some(freq);
+        Ok(())
     }
 
     /// Sets connection and data transfer timeouts (in millisecs) for updater
     /// http call. 0 for no timeout, -1 for defaults
-    pub fn set_updater_data_url_timeout(&self, conn_timeout: i32, data_transfer_timeout: i32) -> Option<WurflError> {
-        unsafe {
-            // wurfl_error wurfl_updater_set_data_url_timeouts(wurfl_handle hwurfl, int connection_timeout, int data_transfer_timeout);
-            if wurfl_updater_set_data_url_timeouts(self.wurfl, conn_timeout, data_transfer_timeout) != WURFL_OK {
-                let err_msg = Wurfl::get_error_message(self.wurfl);
-                return Some(WurflError { msg: err_msg });
-            }
+    pub fn updater_set_data_url_timeouts(&self, conn_timeout: i32, data_transfer_timeout: i32) -> Result<(), WurflError> {
+        // wurfl_error wurfl_updater_set_data_url_timeouts(wurfl_handle hwurfl, int connection_timeout, int data_transfer_timeout);
+        if unsafe { wurfl_updater_set_data_url_timeouts(self.wurfl, conn_timeout, data_transfer_timeout) } != WURFL_OK {
+            return Err(WurflError::from_handle(self.wurfl));
         }
-        return None;
+        lock(&self.engine.updater.settings).timeouts = Some((conn_timeout, data_transfer_timeout));
+        Ok(())
     }
 
-    /// Sets the updater log file path
-    pub fn set_updater_log_path(&self, log_file: &str) -> Option<WurflError> {
-        let c_log = match CString::new(log_file) {
-            Ok(cl) => cl,
-            Err(_) => return Some(WurflError { msg: "Unable to create C string for log path".to_string() }),
-        };
-        unsafe {
-            if wurfl_updater_set_log_path(self.wurfl, c_log.as_ptr()) != WURFL_OK {
-                let err_msg = Wurfl::get_error_message(self.wurfl);
-                return Some(WurflError { msg: err_msg });
-            }
+    /// Sets the updater log file path. The file is written by the wrapper from the updater log lines it receives
+    pub fn updater_set_log_path(&self, log_file: &str) -> Result<(), WurflError> {
+        if log_file.contains('\0') {
+            return Err(WurflError::new(WurflErrorKind::InvalidString, "Unable to create C string for log path".to_string()));
         }
-        return None;
+        // the log file is written by the log sink from the lines routed to Rust, libwurfl does not write it
+        self.engine.updater_log_sink.set_log_path(log_file);
+        self.install_updater_log_trampoline()
     }
 
     /// Start updater process once and wait for termination
-    pub fn updater_runonce(&self) -> Option<WurflError> {
+    pub fn updater_run_once(&self) -> Result<(), WurflError> {
         if unsafe { wurfl_updater_runonce(self.wurfl) } != WURFL_OK {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Some(WurflError { msg: err_msg });
+            return Err(WurflError::from_handle(self.wurfl));
         }
-        return None;
+        Ok(())
     }
 
-    /// Starts periodic updater execution
-    pub fn updater_start(&self) -> Option<WurflError> {
-        if unsafe { wurfl_updater_start(self.wurfl) } != WURFL_OK {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Some(WurflError { msg: err_msg });
-        }
-        return None;
+    #[deprecated(note = "use updater_set_data_url, which returns a Result")]
+    pub fn set_updater_data_url(&self, data_url: &str) -> Option<WurflError> {
+        self.updater_set_data_url(data_url).err()
     }
 
-    /// Stops updater execution
-    pub fn updater_stop(&self) -> Option<WurflError> {
-        if unsafe { wurfl_updater_stop(self.wurfl) } != WURFL_OK {
-            let err_msg = Wurfl::get_error_message(self.wurfl);
-            return Some(WurflError { msg: err_msg });
-        }
-        return None;
+    #[deprecated(note = "use updater_set_data_frequency, which returns a Result")]
+    pub fn set_updater_data_frequency(&self, freq: WurflUpdaterFrequency) -> Option<WurflError> {
+        self.updater_set_data_frequency(freq).err()
     }
 
-    pub fn get_important_headers(&self) -> &HashMap<String, CString> {
-        return &self.important_header_cstring_names;
+    #[deprecated(note = "use updater_set_data_url_timeouts, which returns a Result")]
+    pub fn set_updater_data_url_timeout(&self, conn_timeout: i32, data_transfer_timeout: i32) -> Option<WurflError> {
+        self.updater_set_data_url_timeouts(conn_timeout, data_transfer_timeout).err()
     }
 
-}
-// END UPDATER METHODS ------------------------------------------------------------------------
+    #[deprecated(note = "use updater_set_log_path, which returns a Result")]
+    pub fn set_updater_log_path(&self, log_file: &str) -> Option<WurflError> {
+        self.updater_set_log_path(log_file).err()
+    }
 
-impl Drop for Wurfl {
-    fn drop(&mut self) {
-        //println!("Destroying... WURFL engine");
-        unsafe { wurfl_destroy(self.wurfl) };
+    #[deprecated(note = "use updater_run_once, which returns a Result")]
+    pub fn updater_runonce(&self) -> Option<WurflError> {
+        self.updater_run_once().err()
+    }
+
+    /// Returns a copy of the important header names, which are replaced when the engine is reloaded
+    #[deprecated(note = "use important_header_names, which does not expose C strings")]
+    pub fn get_important_headers(&self) -> HashMap<String, CString> {
+        return self.important_header_table().cstring_names.clone();
     }
+
 }
+// END UPDATER METHODS ------------------------------------------------------------------------
 
 // makes wurfl (and it internal objects) transferable between threads
 unsafe impl Send for Wurfl {}